@@ -0,0 +1,131 @@
+//! Typed request bodies for [`RequestConfig::body_typed`](crate::RequestConfig::body_typed),
+//! and typed response bodies for [`CurlResponse::parse_as`](crate::CurlResponse::parse_as)
+//!
+//! Implement [`IntoBody`] to send your own formats (protobuf, msgpack, ...):
+//!
+//! ```
+//! use bcurl::body::IntoBody;
+//! use bcurl::{CurlError, RequestConfig};
+//!
+//! struct Csv(Vec<[String; 2]>);
+//!
+//! impl IntoBody for Csv {
+//!     fn into_body(self) -> Result<(Vec<u8>, Option<String>), CurlError> {
+//!         let text: String = self.0.iter().map(|row| row.join(",") + "\n").collect();
+//!         Ok((text.into_bytes(), Some("text/csv".to_string())))
+//!     }
+//! }
+//!
+//! let config = RequestConfig::new("https://example.com/import")
+//!     .body_typed(Csv(vec![["a".into(), "1".into()]]))
+//!     .unwrap();
+//! assert_eq!(config.body_bytes.as_deref(), Some(&b"a,1\n"[..]));
+//! ```
+
+use crate::CurlError;
+
+/// A value that can be sent as a request body
+pub trait IntoBody {
+    /// Serialize into the body bytes and the `Content-Type` to send with them,
+    /// unless the request already sets one
+    fn into_body(self) -> Result<(Vec<u8>, Option<String>), CurlError>;
+}
+
+impl IntoBody for String {
+    fn into_body(self) -> Result<(Vec<u8>, Option<String>), CurlError> {
+        Ok((
+            self.into_bytes(),
+            Some("text/plain; charset=utf-8".to_string()),
+        ))
+    }
+}
+
+impl IntoBody for &str {
+    fn into_body(self) -> Result<(Vec<u8>, Option<String>), CurlError> {
+        self.to_string().into_body()
+    }
+}
+
+impl IntoBody for Vec<u8> {
+    fn into_body(self) -> Result<(Vec<u8>, Option<String>), CurlError> {
+        Ok((self, Some("application/octet-stream".to_string())))
+    }
+}
+
+impl IntoBody for &[u8] {
+    fn into_body(self) -> Result<(Vec<u8>, Option<String>), CurlError> {
+        self.to_vec().into_body()
+    }
+}
+
+/// Serializes the wrapped value as a JSON body
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone)]
+pub struct Json<T>(pub T);
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> IntoBody for Json<T> {
+    fn into_body(self) -> Result<(Vec<u8>, Option<String>), CurlError> {
+        let bytes = serde_json::to_vec(&self.0)
+            .map_err(|e| CurlError::InvalidConfig(format!("cannot serialize JSON body: {}", e)))?;
+        Ok((bytes, Some("application/json".to_string())))
+    }
+}
+
+/// A format that response bodies can be parsed from, for
+/// [`CurlResponse::parse_as`](crate::CurlResponse::parse_as).
+///
+/// Implement it on a marker type to plug in your own format:
+///
+/// ```
+/// use bcurl::body::BodyDeserializer;
+/// use bcurl::{CurlError, CurlResponse};
+///
+/// /// `key=value` lines
+/// struct Pairs;
+///
+/// impl BodyDeserializer<Vec<(String, String)>> for Pairs {
+///     fn deserialize(body: &[u8]) -> Result<Vec<(String, String)>, CurlError> {
+///         let text = std::str::from_utf8(body)
+///             .map_err(|e| CurlError::DeserializeError(e.to_string()))?;
+///         text.lines()
+///             .map(|line| {
+///                 let (key, value) = line
+///                     .split_once('=')
+///                     .ok_or_else(|| CurlError::DeserializeError(format!("no '=' in '{}'", line)))?;
+///                 Ok((key.to_string(), value.to_string()))
+///             })
+///             .collect()
+///     }
+/// }
+///
+/// let response = CurlResponse {
+///     body_bytes: b"a=1\nb=2".to_vec(),
+///     ..Default::default()
+/// };
+/// let pairs = response.parse_as::<_, Pairs>().unwrap();
+/// assert_eq!(pairs[1], ("b".to_string(), "2".to_string()));
+/// ```
+pub trait BodyDeserializer<T> {
+    /// Parse the body bytes, failing with [`CurlError::DeserializeError`]
+    fn deserialize(body: &[u8]) -> Result<T, CurlError>;
+}
+
+/// Parses JSON bodies with serde_json
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy)]
+pub struct JsonDeserializer;
+
+#[cfg(feature = "serde")]
+impl<T: serde::de::DeserializeOwned> BodyDeserializer<T> for JsonDeserializer {
+    fn deserialize(body: &[u8]) -> Result<T, CurlError> {
+        serde_json::from_slice(body)
+            .map_err(|e| CurlError::DeserializeError(format!("JSON: {}", e)))
+    }
+}
+
+/// Whether a `Content-Type` is JSON: `application/json` or a `+json` type
+pub(crate) fn is_json(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime.eq_ignore_ascii_case("application/json") || mime.to_ascii_lowercase().ends_with("+json")
+}
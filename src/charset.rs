@@ -0,0 +1,139 @@
+//! Character set conversion for response bodies
+//!
+//! Labels are the names used in `Content-Type`, such as `shift_jis`,
+//! `iso-8859-1` or `windows-1252`, resolved per the WHATWG Encoding Standard.
+//! Legacy charsets need the `charset` feature; without it only UTF-8 is known.
+
+use crate::CurlError;
+
+#[cfg(feature = "charset")]
+use encoding_rs::{EncoderResult, Encoding};
+
+/// Whether `label` names a charset this build can convert
+pub fn is_supported(label: &str) -> bool {
+    #[cfg(feature = "charset")]
+    {
+        Encoding::for_label(label.trim().as_bytes()).is_some()
+    }
+    #[cfg(not(feature = "charset"))]
+    {
+        is_utf8(label)
+    }
+}
+
+/// Fail with [`CurlError::InvalidConfig`] unless `label` is supported
+pub(crate) fn ensure_supported(label: &str) -> Result<(), CurlError> {
+    if is_supported(label) {
+        Ok(())
+    } else {
+        Err(unsupported(label))
+    }
+}
+
+/// Whether decoding from `label` changes the bytes, i.e. it names a supported
+/// charset other than UTF-8
+pub fn converts(label: &str) -> bool {
+    #[cfg(feature = "charset")]
+    {
+        Encoding::for_label(label.trim().as_bytes())
+            .is_some_and(|encoding| encoding != encoding_rs::UTF_8)
+    }
+    #[cfg(not(feature = "charset"))]
+    {
+        let _ = label;
+        false
+    }
+}
+
+/// Decode `bytes` from the charset `label`, replacing malformed sequences with
+/// U+FFFD. Returns `None` if the charset isn't supported.
+pub fn decode(bytes: &[u8], label: &str) -> Option<String> {
+    #[cfg(feature = "charset")]
+    {
+        let encoding = Encoding::for_label(label.trim().as_bytes())?;
+        Some(encoding.decode_without_bom_handling(bytes).0.into_owned())
+    }
+    #[cfg(not(feature = "charset"))]
+    {
+        is_utf8(label).then(|| String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Encode `text` into the charset `label`. Characters the charset can't
+/// represent become `?`.
+pub fn encode(text: &str, label: &str) -> Result<Vec<u8>, CurlError> {
+    #[cfg(feature = "charset")]
+    {
+        let encoding = Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| unsupported(label))?
+            // UTF-16 can't be produced by encoding_rs; it falls back to UTF-8
+            .output_encoding();
+        let mut encoder = encoding.new_encoder();
+        let mut out = Vec::with_capacity(text.len());
+        let mut rest = text;
+        loop {
+            let needed = encoder
+                .max_buffer_length_from_utf8_without_replacement(rest.len())
+                .unwrap_or(rest.len() * 4);
+            out.reserve(needed);
+            let (result, read) =
+                encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut out, true);
+            rest = &rest[read..];
+            match result {
+                EncoderResult::InputEmpty => return Ok(out),
+                EncoderResult::Unmappable(_) => out.push(b'?'),
+                EncoderResult::OutputFull => {}
+            }
+        }
+    }
+    #[cfg(not(feature = "charset"))]
+    {
+        ensure_supported(label)?;
+        Ok(text.as_bytes().to_vec())
+    }
+}
+
+fn unsupported(label: &str) -> CurlError {
+    if cfg!(feature = "charset") {
+        CurlError::InvalidConfig(format!("unknown charset '{}'", label))
+    } else {
+        CurlError::InvalidConfig(format!(
+            "charset '{}' needs bcurl built with the `charset` feature",
+            label
+        ))
+    }
+}
+
+#[cfg(not(feature = "charset"))]
+fn is_utf8(label: &str) -> bool {
+    let label = label.trim();
+    label.eq_ignore_ascii_case("utf-8") || label.eq_ignore_ascii_case("utf8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_always_supported() {
+        assert!(is_supported("UTF-8"));
+        assert!(!converts("utf-8"));
+        assert_eq!(decode(b"caf\xc3\xa9", "utf-8").as_deref(), Some("café"));
+        assert_eq!(encode("café", "utf-8").unwrap(), b"caf\xc3\xa9");
+    }
+
+    #[cfg(feature = "charset")]
+    #[test]
+    fn test_legacy_charsets() {
+        // "テスト" in Shift-JIS
+        let sjis = [0x83, 0x65, 0x83, 0x58, 0x83, 0x67];
+        assert_eq!(decode(&sjis, "Shift_JIS").as_deref(), Some("テスト"));
+        assert_eq!(decode(b"caf\xe9", "iso-8859-1").as_deref(), Some("café"));
+        assert!(converts("iso-8859-1"));
+        assert_eq!(encode("café ✓", "latin1").unwrap(), b"caf\xe9 ?");
+        assert!(matches!(
+            encode("x", "no-such-charset"),
+            Err(CurlError::InvalidConfig(_))
+        ));
+    }
+}
@@ -0,0 +1,419 @@
+//! MD5, SHA-1 and SHA-256 digests for verifying downloads
+//!
+//! Implemented here rather than pulled in as crates to keep the binary small;
+//! they are checked against the published test vectors below.
+
+use crate::CurlError;
+use std::fmt;
+use std::io::Read;
+use std::str::FromStr;
+
+/// A digest algorithm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Md5,
+    Sha1,
+    Sha256,
+}
+
+impl Algorithm {
+    /// Lowercase name, as used in `--checksum` prefixes
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Algorithm::Md5 => "md5",
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// Digest of `data` in one go
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        let mut hasher = Hasher::new(self);
+        hasher.update(data);
+        hasher.finalize()
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Algorithm {
+    type Err = CurlError;
+
+    /// Parse a name (case-insensitive); `sha-1` and `sha-256` are accepted too, as
+    /// written in `Digest` headers
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "md5" => Ok(Algorithm::Md5),
+            "sha1" | "sha-1" | "sha" => Ok(Algorithm::Sha1),
+            "sha256" | "sha-256" => Ok(Algorithm::Sha256),
+            _ => Err(CurlError::InvalidConfig(format!(
+                "Unknown checksum algorithm: {}",
+                name
+            ))),
+        }
+    }
+}
+
+/// An expected digest, see [`RequestConfig::verify_checksum`](crate::RequestConfig::verify_checksum)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksum {
+    pub algorithm: Algorithm,
+    /// Lowercase hex
+    pub hex: String,
+}
+
+impl Checksum {
+    /// `hex` must be the full digest for `algorithm`, in either case
+    pub fn new(algorithm: Algorithm, hex: &str) -> Result<Self, CurlError> {
+        let hex = hex.trim().to_ascii_lowercase();
+        if hex.len() != Hasher::new(algorithm).output_len() * 2
+            || !hex.bytes().all(|b| b.is_ascii_hexdigit())
+        {
+            return Err(CurlError::InvalidConfig(format!(
+                "'{}' is not a {} digest",
+                hex, algorithm
+            )));
+        }
+        Ok(Self { algorithm, hex })
+    }
+
+    /// Parse `<algorithm>:<hex>`, e.g. `sha256:9f86d0...`
+    ///
+    /// ```
+    /// use bcurl::checksum::{Algorithm, Checksum};
+    ///
+    /// let checksum = Checksum::parse("MD5:D41D8CD98F00B204E9800998ECF8427E").unwrap();
+    /// assert_eq!(checksum.algorithm, Algorithm::Md5);
+    /// assert!(checksum.matches(b""));
+    /// ```
+    pub fn parse(spec: &str) -> Result<Self, CurlError> {
+        let (algorithm, hex) = spec.split_once(':').ok_or_else(|| {
+            CurlError::InvalidConfig(format!("checksum '{}' must be <algorithm>:<hex>", spec))
+        })?;
+        Self::new(algorithm.parse()?, hex)
+    }
+
+    /// Whether `data` has this digest
+    pub fn matches(&self, data: &[u8]) -> bool {
+        to_hex(&self.algorithm.digest(data)) == self.hex
+    }
+
+    /// Fail with [`CurlError::ChecksumMismatch`] unless `actual` (a digest made
+    /// with this algorithm) is the expected one
+    pub(crate) fn check(&self, actual: &[u8]) -> Result<(), CurlError> {
+        let actual = to_hex(actual);
+        if actual == self.hex {
+            return Ok(());
+        }
+        Err(CurlError::ChecksumMismatch {
+            algorithm: self.algorithm,
+            expected: self.hex.clone(),
+            actual,
+        })
+    }
+
+    /// Hash everything `reader` yields, a chunk at a time, and check the result
+    pub(crate) fn check_reader(&self, mut reader: impl Read) -> Result<(), CurlError> {
+        let mut hasher = Hasher::new(self.algorithm);
+        let mut buffer = [0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+        }
+        self.check(&hasher.finalize())
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.algorithm, self.hex)
+    }
+}
+
+/// Lowercase hex of `bytes`
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Incremental digest of data fed in pieces
+#[derive(Clone)]
+pub struct Hasher {
+    state: State,
+    block: [u8; 64],
+    buffered: usize,
+    length: u64,
+}
+
+#[derive(Clone)]
+enum State {
+    Md5([u32; 4]),
+    Sha1([u32; 5]),
+    Sha256([u32; 8]),
+}
+
+impl Hasher {
+    /// Start a digest with `algorithm`
+    pub fn new(algorithm: Algorithm) -> Self {
+        let state = match algorithm {
+            Algorithm::Md5 => State::Md5([0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476]),
+            Algorithm::Sha1 => {
+                State::Sha1([0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476, 0xc3d2e1f0])
+            }
+            Algorithm::Sha256 => State::Sha256([
+                0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+                0x5be0cd19,
+            ]),
+        };
+        Self {
+            state,
+            block: [0; 64],
+            buffered: 0,
+            length: 0,
+        }
+    }
+
+    fn output_len(&self) -> usize {
+        match self.state {
+            State::Md5(_) => 16,
+            State::Sha1(_) => 20,
+            State::Sha256(_) => 32,
+        }
+    }
+
+    /// Feed in the next piece of data
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length = self.length.wrapping_add(data.len() as u64);
+        while !data.is_empty() {
+            let take = (64 - self.buffered).min(data.len());
+            self.block[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+            if self.buffered == 64 {
+                let block = self.block;
+                self.compress(&block);
+                self.buffered = 0;
+            }
+        }
+    }
+
+    /// Finish the digest and return its bytes
+    pub fn finalize(mut self) -> Vec<u8> {
+        let bits = self.length.wrapping_mul(8);
+        let length = match self.state {
+            State::Md5(_) => bits.to_le_bytes(),
+            _ => bits.to_be_bytes(),
+        };
+        let padding = if self.buffered < 56 {
+            56 - self.buffered
+        } else {
+            120 - self.buffered
+        };
+        let mut tail = vec![0u8; padding];
+        tail[0] = 0x80;
+        tail.extend_from_slice(&length);
+        self.update(&tail);
+
+        match self.state {
+            State::Md5(h) => h.iter().flat_map(|w| w.to_le_bytes()).collect(),
+            State::Sha1(h) => h.iter().flat_map(|w| w.to_be_bytes()).collect(),
+            State::Sha256(h) => h.iter().flat_map(|w| w.to_be_bytes()).collect(),
+        }
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        match self.state {
+            State::Md5(ref mut h) => md5_compress(h, block),
+            State::Sha1(ref mut h) => sha1_compress(h, block),
+            State::Sha256(ref mut h) => sha256_compress(h, block),
+        }
+    }
+}
+
+const MD5_SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+const MD5_K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+
+fn md5_compress(h: &mut [u32; 4], block: &[u8; 64]) {
+    let m: Vec<u32> = block
+        .chunks_exact(4)
+        .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect();
+    let [mut a, mut b, mut c, mut d] = *h;
+    for i in 0..64 {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), (7 * i) % 16),
+        };
+        let shift = MD5_SHIFTS[(i / 16) * 4 + i % 4];
+        let rotated = a
+            .wrapping_add(f)
+            .wrapping_add(MD5_K[i])
+            .wrapping_add(m[g])
+            .rotate_left(shift);
+        a = d;
+        d = c;
+        c = b;
+        b = b.wrapping_add(rotated);
+    }
+    h[0] = h[0].wrapping_add(a);
+    h[1] = h[1].wrapping_add(b);
+    h[2] = h[2].wrapping_add(c);
+    h[3] = h[3].wrapping_add(d);
+}
+
+fn sha1_compress(h: &mut [u32; 5], block: &[u8; 64]) {
+    let mut w = [0u32; 80];
+    for (i, c) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes([c[0], c[1], c[2], c[3]]);
+    }
+    for i in 16..80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e] = *h;
+    for (i, &word) in w.iter().enumerate() {
+        let (f, k) = match i / 20 {
+            0 => ((b & c) | (!b & d), 0x5a827999),
+            1 => (b ^ c ^ d, 0x6ed9eba1),
+            2 => ((b & c) | (b & d) | (c & d), 0x8f1bbcdc),
+            _ => (b ^ c ^ d, 0xca62c1d6),
+        };
+        let temp = a
+            .rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(word);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = temp;
+    }
+    for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+        *state = state.wrapping_add(value);
+    }
+}
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+fn sha256_compress(h: &mut [u32; 8], block: &[u8; 64]) {
+    let mut w = [0u32; 64];
+    for (i, c) in block.chunks_exact(4).enumerate() {
+        w[i] = u32::from_be_bytes([c[0], c[1], c[2], c[3]]);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+    let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = *h;
+    for (&k, &word) in SHA256_K.iter().zip(&w) {
+        let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+        let ch = (e & f) ^ (!e & g);
+        let temp1 = hh
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(k)
+            .wrapping_add(word);
+        let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+        let maj = (a & b) ^ (a & c) ^ (b & c);
+        let temp2 = s0.wrapping_add(maj);
+        hh = g;
+        g = f;
+        f = e;
+        e = d.wrapping_add(temp1);
+        d = c;
+        c = b;
+        b = a;
+        a = temp1.wrapping_add(temp2);
+    }
+    for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+        *state = state.wrapping_add(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_digests() {
+        let cases = [
+            (Algorithm::Md5, "", "d41d8cd98f00b204e9800998ecf8427e"),
+            (Algorithm::Md5, "abc", "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                Algorithm::Sha1,
+                "abc",
+                "a9993e364706816aba3e25717850c26c9cd0d89d",
+            ),
+            (
+                Algorithm::Sha256,
+                "abc",
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                Algorithm::Sha256,
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+        ];
+        for (algorithm, input, expected) in cases {
+            assert_eq!(to_hex(&algorithm.digest(input.as_bytes())), expected);
+        }
+    }
+
+    #[test]
+    fn test_incremental_matches_one_shot() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        for algorithm in [Algorithm::Md5, Algorithm::Sha1, Algorithm::Sha256] {
+            let mut hasher = Hasher::new(algorithm);
+            for chunk in data.chunks(63) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.finalize(), algorithm.digest(&data));
+        }
+    }
+
+    #[test]
+    fn test_parse_checksum() {
+        assert!(Checksum::parse("sha256:abc").is_err());
+        assert!(Checksum::parse("crc32:00000000").is_err());
+        assert!(Checksum::parse("d41d8cd98f00b204e9800998ecf8427e").is_err());
+        let checksum = Checksum::parse("sha1:A9993E364706816ABA3E25717850C26C9CD0D89D").unwrap();
+        assert_eq!(
+            checksum.to_string(),
+            "sha1:a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert!(checksum.matches(b"abc"));
+    }
+}
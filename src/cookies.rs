@@ -0,0 +1,469 @@
+//! A cookie jar for [`MinimalCurl::with_cookies`](crate::MinimalCurl::with_cookies)
+//!
+//! Follows the storage and matching rules of RFC 6265: a cookie without a
+//! `Domain` goes back only to the host that set it, `Path` scopes it to part of
+//! the site, and `Secure` cookies only travel over HTTPS. There is no public
+//! suffix list, so a server can set a cookie for a parent domain such as
+//! `co.uk`; only use the jar with servers you trust.
+//!
+//! [`load_cookies`] and [`save_cookies`] read and write the Netscape
+//! `cookies.txt` format used by curl and browser extensions, for `-b` and `-c`.
+
+use crate::CurlError;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// First line of a Netscape cookie file, which some readers check for
+const NETSCAPE_HEADER: &str = "# Netscape HTTP Cookie File";
+
+/// Prefix curl and browsers put on the domain of `HttpOnly` cookies
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// A stored cookie
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// Lowercase host or domain, without a leading dot
+    pub domain: String,
+    /// Sent only to `domain` itself rather than also to its subdomains
+    pub host_only: bool,
+    pub path: String,
+    /// Sent only over HTTPS
+    pub secure: bool,
+    pub http_only: bool,
+    /// When it expires; `None` for a session cookie
+    pub expires: Option<SystemTime>,
+}
+
+impl Cookie {
+    /// Whether the cookie goes with a request to `url`
+    fn matches(&self, url: &url::Url, now: SystemTime) -> bool {
+        let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+        domain_ok
+            && path_match(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+            && self.expires.is_none_or(|expires| expires > now)
+    }
+}
+
+/// Cookies received with responses, to send back with later requests
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    /// An empty jar
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store the cookie of a `Set-Cookie` header received from `url`, replacing
+    /// one with the same name, domain and path. A cookie that has already
+    /// expired deletes that one instead. Malformed cookies, and cookies for
+    /// a domain `url` isn't part of, are ignored.
+    pub fn store(&mut self, url: &str, set_cookie: &str) {
+        let Ok(url) = url::Url::parse(url) else {
+            return;
+        };
+        let Some(cookie) = parse_set_cookie(&url, set_cookie, SystemTime::now()) else {
+            return;
+        };
+        self.insert(cookie);
+    }
+
+    /// Add a cookie, replacing one with the same name, domain and path. An
+    /// expired cookie deletes that one instead.
+    pub fn insert(&mut self, cookie: Cookie) {
+        self.cookies.retain(|stored| {
+            (&stored.name, &stored.domain, &stored.path)
+                != (&cookie.name, &cookie.domain, &cookie.path)
+        });
+        if cookie
+            .expires
+            .is_none_or(|expires| expires > SystemTime::now())
+        {
+            self.cookies.push(cookie);
+        }
+    }
+
+    /// The `Cookie` header value for a request to `url`, if any cookie goes
+    /// with it. Cookies with longer paths come first, as RFC 6265 suggests.
+    pub fn header(&self, url: &str) -> Option<String> {
+        let url = url::Url::parse(url).ok()?;
+        let now = SystemTime::now();
+        let mut matching: Vec<&Cookie> = self
+            .cookies
+            .iter()
+            .filter(|cookie| cookie.matches(&url, now))
+            .collect();
+        if matching.is_empty() {
+            return None;
+        }
+        matching.sort_by_key(|cookie| std::cmp::Reverse(cookie.path.len()));
+        let pairs: Vec<String> = matching
+            .iter()
+            .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    /// The stored cookies, in the order they were first set
+    pub fn cookies(&self) -> &[Cookie] {
+        &self.cookies
+    }
+
+    /// Forget every cookie
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+}
+
+impl Extend<Cookie> for CookieJar {
+    fn extend<I: IntoIterator<Item = Cookie>>(&mut self, cookies: I) {
+        for cookie in cookies {
+            self.insert(cookie);
+        }
+    }
+}
+
+impl FromIterator<Cookie> for CookieJar {
+    fn from_iter<I: IntoIterator<Item = Cookie>>(cookies: I) -> Self {
+        let mut jar = Self::new();
+        jar.extend(cookies);
+        jar
+    }
+}
+
+/// Read the cookies of a Netscape `cookies.txt` file. Comment lines and
+/// lines that aren't seven tab-separated fields are skipped; an expiry of 0
+/// means a session cookie.
+pub fn load_cookies(path: &str) -> Result<Vec<Cookie>, CurlError> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(text.lines().filter_map(parse_netscape_line).collect())
+}
+
+/// Write the jar's unexpired cookies to `path` in the Netscape `cookies.txt`
+/// format, replacing the file
+pub fn save_cookies(path: &str, jar: &CookieJar) -> Result<(), CurlError> {
+    let now = SystemTime::now();
+    let mut text = format!("{}\n# Written by bcurl\n\n", NETSCAPE_HEADER);
+    for cookie in jar.cookies() {
+        if cookie.expires.is_some_and(|expires| expires <= now) {
+            continue;
+        }
+        let expires = cookie.expires.map_or(0, |expires| {
+            expires
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs())
+        });
+        text.push_str(&format!(
+            "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            if cookie.http_only {
+                HTTP_ONLY_PREFIX
+            } else {
+                ""
+            },
+            if cookie.host_only { "" } else { "." },
+            cookie.domain,
+            netscape_flag(!cookie.host_only),
+            cookie.path,
+            netscape_flag(cookie.secure),
+            expires,
+            cookie.name,
+            cookie.value
+        ));
+    }
+    std::fs::write(path, text)?;
+    Ok(())
+}
+
+/// A cookie from one line of a Netscape cookie file: domain, whether
+/// subdomains match, path, secure, expiry in Unix seconds, name and value
+fn parse_netscape_line(line: &str) -> Option<Cookie> {
+    let line = line.trim_end_matches('\r');
+    let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+        Some(line) => (line, true),
+        None => (line, false),
+    };
+    if line.starts_with('#') {
+        return None;
+    }
+    let fields: Vec<&str> = line.split('\t').collect();
+    let [domain, subdomains, path, secure, expires, name, value] = fields[..] else {
+        return None;
+    };
+    let expires: u64 = expires.parse().ok()?;
+    let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+    if domain.is_empty() || name.is_empty() {
+        return None;
+    }
+    Some(Cookie {
+        name: name.to_string(),
+        value: value.to_string(),
+        domain,
+        host_only: !subdomains.eq_ignore_ascii_case("TRUE"),
+        path: path.to_string(),
+        secure: secure.eq_ignore_ascii_case("TRUE"),
+        http_only,
+        expires: (expires != 0).then(|| UNIX_EPOCH + Duration::from_secs(expires)),
+    })
+}
+
+fn netscape_flag(set: bool) -> &'static str {
+    if set {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
+/// Parse a `Set-Cookie` value received from `url` (RFC 6265 section 5.2)
+fn parse_set_cookie(url: &url::Url, set_cookie: &str, now: SystemTime) -> Option<Cookie> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    let mut parts = set_cookie.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let (name, value) = (name.trim(), value.trim());
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.to_string(),
+        domain: host.clone(),
+        host_only: true,
+        path: default_path(url.path()),
+        secure: false,
+        http_only: false,
+        expires: None,
+    };
+    let mut max_age = None;
+    for attribute in parts {
+        let (key, value) = match attribute.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => (attribute.trim(), ""),
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "expires" => {
+                // Also accept the dashed dates of the original Netscape spec
+                if let Some(date) = crate::parse_http_date(&value.replace('-', " ")) {
+                    cookie.expires = Some(date);
+                }
+            }
+            "max-age" => {
+                if let Ok(seconds) = value.parse::<i64>() {
+                    max_age = Some(seconds);
+                }
+            }
+            "domain" if !value.is_empty() => {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                if !domain_match(&host, &domain) {
+                    return None;
+                }
+                cookie.host_only = false;
+                cookie.domain = domain;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            _ => {}
+        }
+    }
+    // Max-Age wins over Expires
+    if let Some(seconds) = max_age {
+        cookie.expires = Some(match u64::try_from(seconds) {
+            Ok(seconds) if seconds > 0 => now + Duration::from_secs(seconds),
+            _ => SystemTime::UNIX_EPOCH,
+        });
+    }
+    // Only a secure origin may set a secure cookie
+    if cookie.secure && url.scheme() != "https" {
+        return None;
+    }
+    Some(cookie)
+}
+
+/// Whether `host` is `domain` or one of its subdomains. IP addresses only
+/// match themselves.
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    let is_ip = host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[');
+    !is_ip
+        && host
+            .strip_suffix(domain)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+/// Whether a cookie with path `cookie_path` goes with a request for `path`
+fn path_match(path: &str, cookie_path: &str) -> bool {
+    path == cookie_path
+        || (path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || path[cookie_path.len()..].starts_with('/')))
+}
+
+/// The path a cookie gets without a `Path` attribute: the request path up to
+/// its last `/`
+fn default_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(end) => path[..end].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scoping() {
+        let mut jar = CookieJar::new();
+        jar.store("http://example.com/app/login", "session=abc; HttpOnly");
+        jar.store("http://example.com/", "theme=dark; Domain=.Example.com");
+        jar.store("https://example.com/", "token=t; Path=/api; Secure");
+
+        assert_eq!(
+            jar.header("http://example.com/app/page").as_deref(),
+            Some("session=abc; theme=dark")
+        );
+        // Host-only cookies stay on their host, domain cookies don't
+        assert_eq!(
+            jar.header("http://www.example.com/app/").as_deref(),
+            Some("theme=dark")
+        );
+        assert_eq!(
+            jar.header("http://example.com/apple").as_deref(),
+            Some("theme=dark")
+        );
+        // Secure cookies need HTTPS, and longer paths go first
+        assert_eq!(
+            jar.header("http://example.com/api/v1").as_deref(),
+            Some("theme=dark")
+        );
+        assert_eq!(
+            jar.header("https://example.com/api/v1").as_deref(),
+            Some("token=t; theme=dark")
+        );
+        assert_eq!(jar.header("http://other.example/"), None);
+    }
+
+    #[test]
+    fn test_rejected_cookies() {
+        let mut jar = CookieJar::new();
+        // Another site's domain, a secure cookie over HTTP, no name
+        jar.store("http://example.com/", "a=1; Domain=other.com");
+        jar.store("http://example.com/", "b=2; Secure");
+        jar.store("http://example.com/", "=3");
+        jar.store("http://example.com/", "garbage");
+        // Subdomains can't be set from an IP address
+        jar.store("http://127.0.0.1/", "c=4; Domain=0.0.1");
+        assert!(jar.cookies().is_empty());
+    }
+
+    #[test]
+    fn test_replace_and_expire() {
+        let mut jar = CookieJar::new();
+        jar.store("http://example.com/", "id=1");
+        jar.store("http://example.com/", "id=2; Max-Age=3600");
+        assert_eq!(jar.header("http://example.com/").as_deref(), Some("id=2"));
+        assert!(jar.cookies()[0].expires.is_some());
+
+        jar.store("http://example.com/", "id=gone; Max-Age=0");
+        assert_eq!(jar.header("http://example.com/"), None);
+
+        jar.store(
+            "http://example.com/",
+            "old=1; Expires=Wed, 21-Oct-2015 07:28:00 GMT",
+        );
+        jar.store(
+            "http://example.com/",
+            "new=1; Expires=Fri, 01 Jan 2100 00:00:00 GMT",
+        );
+        assert_eq!(jar.header("http://example.com/").as_deref(), Some("new=1"));
+    }
+
+    const COOKIE_FILE: &str = "# Netscape HTTP Cookie File\n\
+        # https://curl.se/docs/http-cookies.html\n\
+        \n\
+        .example.com\tTRUE\t/\tFALSE\t0\ttheme\tdark\n\
+        #HttpOnly_example.com\tFALSE\t/app\tTRUE\t4102444800\tsession\tabc\r\n\
+        example.com\tFALSE\t/\tFALSE\t0\tempty\t\n\
+        broken line\n";
+
+    #[test]
+    fn test_load_cookies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cookies.txt");
+        std::fs::write(&path, COOKIE_FILE).unwrap();
+
+        let cookies = load_cookies(path.to_str().unwrap()).unwrap();
+        assert_eq!(cookies.len(), 3);
+        assert_eq!(cookies[0].domain, "example.com");
+        assert!(!cookies[0].host_only);
+        assert_eq!(cookies[0].expires, None);
+        assert!(cookies[1].http_only && cookies[1].secure && cookies[1].host_only);
+        assert_eq!(cookies[1].path, "/app");
+        assert_eq!(
+            cookies[1].expires,
+            Some(UNIX_EPOCH + Duration::from_secs(4102444800))
+        );
+        assert_eq!(cookies[2].value, "");
+
+        let mut jar = CookieJar::new();
+        jar.extend(cookies);
+        assert_eq!(
+            jar.header("https://example.com/app/x").as_deref(),
+            Some("session=abc; theme=dark; empty=")
+        );
+        assert!(load_cookies(dir.path().join("missing").to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_save_cookies_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cookies.txt");
+        let path = path.to_str().unwrap();
+
+        let mut jar = CookieJar::new();
+        jar.store("http://example.com/", "theme=dark; Domain=example.com");
+        jar.store(
+            "https://example.com/app/login",
+            "session=abc; Secure; HttpOnly; Max-Age=3600",
+        );
+        jar.store("http://example.com/", "gone=1");
+        jar.store("http://example.com/", "gone=1; Max-Age=0");
+        save_cookies(path, &jar).unwrap();
+
+        let saved = std::fs::read_to_string(path).unwrap();
+        assert!(saved.starts_with(NETSCAPE_HEADER));
+        assert!(saved.contains(".example.com\tTRUE\t/\tFALSE\t0\ttheme\tdark\n"));
+        assert!(saved.contains("#HttpOnly_example.com\tFALSE\t/app\tTRUE\t"));
+        assert!(!saved.contains("gone"));
+
+        // Expiry is stored to the second
+        let mut expected = jar.clone();
+        expected.cookies[1].expires = expected.cookies[1].expires.map(|expires| {
+            UNIX_EPOCH + Duration::from_secs(expires.duration_since(UNIX_EPOCH).unwrap().as_secs())
+        });
+        let mut loaded = CookieJar::new();
+        loaded.extend(load_cookies(path).unwrap());
+        assert_eq!(loaded, expected);
+    }
+
+    #[test]
+    fn test_default_path() {
+        assert_eq!(default_path("/"), "/");
+        assert_eq!(default_path("/login"), "/");
+        assert_eq!(default_path("/app/login"), "/app");
+        assert!(path_match("/app", "/app"));
+        assert!(path_match("/app/x", "/app"));
+        assert!(!path_match("/apple", "/app"));
+    }
+}
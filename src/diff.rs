@@ -0,0 +1,193 @@
+//! Line-based unified diff, used to compare two responses
+//!
+//! Implements Myers' O((N+M)D) algorithm, which stays fast when the inputs
+//! are mostly identical (the common case when comparing staging vs prod).
+
+/// Number of unchanged lines shown around each change
+const CONTEXT: usize = 3;
+
+/// A single step of the edit script, holding indices into the old/new lines
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Produce a unified diff between `old` and `new`, or `None` if they are identical.
+///
+/// The labels are used for the `---`/`+++` header lines.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let edits = myers(&a, &b);
+    if edits.iter().all(|e| matches!(e, Edit::Equal(..))) {
+        // Only line endings differ; `lines()` hides those
+        return Some(format!(
+            "--- {}\n+++ {}\n(line endings differ)\n",
+            old_label, new_label
+        ));
+    }
+
+    // Flatten into (tag, old position, new position, line)
+    let mut ops = Vec::with_capacity(edits.len());
+    let (mut a_pos, mut b_pos) = (0, 0);
+    for edit in edits {
+        match edit {
+            Edit::Equal(i, j) => {
+                ops.push((' ', i, j, a[i]));
+                a_pos = i + 1;
+                b_pos = j + 1;
+            }
+            Edit::Delete(i) => {
+                ops.push(('-', i, b_pos, a[i]));
+                a_pos = i + 1;
+            }
+            Edit::Insert(j) => {
+                ops.push(('+', a_pos, j, b[j]));
+                b_pos = j + 1;
+            }
+        }
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    let changes: Vec<usize> = (0..ops.len()).filter(|&i| ops[i].0 != ' ').collect();
+    let mut idx = 0;
+    while idx < changes.len() {
+        // Grow the hunk while the next change is close enough to share context
+        let start = changes[idx].saturating_sub(CONTEXT);
+        let mut last = changes[idx];
+        while idx + 1 < changes.len() && changes[idx + 1] <= last + 2 * CONTEXT {
+            idx += 1;
+            last = changes[idx];
+        }
+        let end = (last + CONTEXT + 1).min(ops.len());
+        let hunk = &ops[start..end];
+
+        let old_count = hunk.iter().filter(|op| op.0 != '+').count();
+        let new_count = hunk.iter().filter(|op| op.0 != '-').count();
+        // An empty range points at the line before it, per the unified format
+        let old_start = hunk[0].1 + usize::from(old_count > 0);
+        let new_start = hunk[0].2 + usize::from(new_count > 0);
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start, old_count, new_start, new_count
+        ));
+        for (tag, _, _, line) in hunk {
+            out.push(*tag);
+            out.push_str(line);
+            out.push('\n');
+        }
+        idx += 1;
+    }
+
+    Some(out)
+}
+
+/// Compute the shortest edit script between two line slices
+fn myers(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    let offset = max as usize;
+    let mut v = vec![0isize; 2 * offset + 2];
+    let mut trace = Vec::new();
+
+    // Forward pass: record the furthest-reaching path for each edit distance
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let idx = (k + max) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // Backtrack through the recorded states to recover the edits
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[(k - 1 + max) as usize] < v[(k + 1 + max) as usize])
+        {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[(prev_k + max) as usize];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Equal((x - 1) as usize, (y - 1) as usize));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert((y - 1) as usize));
+            } else {
+                edits.push(Edit::Delete((x - 1) as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    edits.reverse();
+    edits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_inputs() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "old", "new"), None);
+    }
+
+    #[test]
+    fn test_single_line_change() {
+        let diff = unified_diff("a\nb\nc\n", "a\nx\nc\n", "old", "new").unwrap();
+        assert_eq!(diff, "--- old\n+++ new\n@@ -1,3 +1,3 @@\n a\n-b\n+x\n c\n");
+    }
+
+    #[test]
+    fn test_distant_changes_make_separate_hunks() {
+        let old: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let new: String = (1..=20)
+            .map(|i| match i {
+                2 => "two\n".to_string(),
+                19 => "nineteen\n".to_string(),
+                _ => format!("{}\n", i),
+            })
+            .collect();
+        let diff = unified_diff(&old, &new, "old", "new").unwrap();
+        assert_eq!(diff.matches("@@ -").count(), 2);
+        assert!(diff.contains("@@ -1,5 +1,5 @@\n 1\n-2\n+two\n"));
+        assert!(diff.contains("@@ -16,5 +16,5 @@\n 16\n 17\n 18\n-19\n+nineteen\n 20\n"));
+    }
+
+    #[test]
+    fn test_insert_into_empty() {
+        let diff = unified_diff("", "a\nb\n", "old", "new").unwrap();
+        assert_eq!(diff, "--- old\n+++ new\n@@ -0,0 +1,2 @@\n+a\n+b\n");
+    }
+}
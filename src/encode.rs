@@ -0,0 +1,95 @@
+//! Percent-encoding for the different parts of a URL and for form bodies
+//!
+//! Each context reserves a different set of characters: `/` separates path
+//! segments but is plain data inside a query value, while `&` and `=` are data
+//! in a path but split a query string into pairs. Pick the function matching
+//! where the text ends up. All of them encode non-ASCII text as UTF-8 bytes.
+
+/// Encode a path, keeping `/` so segments stay separated.
+///
+/// Characters that would end the path (`?`, `#`) or break parsing (space, `%`)
+/// are encoded; sub-delimiters such as `&`, `=` and `+` are legal in a path and
+/// kept as they are.
+///
+/// ```
+/// assert_eq!(bcurl::encode::encode_path("/files/a b?.txt"), "/files/a%20b%3F.txt");
+/// ```
+pub fn encode_path(input: &str) -> String {
+    encode(input, |b| {
+        is_unreserved(b) || b"!$&'()*+,;=:@/".contains(&b)
+    })
+}
+
+/// Encode a query-string key or value.
+///
+/// `&`, `=`, `+`, `;` and `#` are encoded so the text can't split or end the
+/// query; `/`, `?`, `:` and `@` are allowed in a query and kept readable.
+///
+/// ```
+/// assert_eq!(bcurl::encode::encode_query("a&b=c/d"), "a%26b%3Dc/d");
+/// ```
+pub fn encode_query(input: &str) -> String {
+    encode(input, |b| is_unreserved(b) || b"!$'()*,:@/?".contains(&b))
+}
+
+/// Encode a field name or value for an `application/x-www-form-urlencoded` body.
+///
+/// Only unreserved characters are left alone. Spaces become `%20`, as curl's
+/// `--data-urlencode` does, rather than `+`; servers decode both.
+///
+/// ```
+/// assert_eq!(bcurl::encode::encode_form("a b&c"), "a%20b%26c");
+/// ```
+pub fn encode_form(input: &str) -> String {
+    encode(input, is_unreserved)
+}
+
+/// Unreserved characters (RFC 3986 §2.3) never need encoding
+#[inline]
+fn is_unreserved(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~')
+}
+
+/// Percent-encode every byte for which `keep` returns false
+fn encode(input: &str, keep: impl Fn(u8) -> bool) -> String {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    let mut out = String::with_capacity(input.len());
+    for &b in input.as_bytes() {
+        if keep(b) {
+            out.push(b as char);
+        } else {
+            out.push('%');
+            out.push(HEX[usize::from(b >> 4)] as char);
+            out.push(HEX[usize::from(b & 0x0F)] as char);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slash_depends_on_context() {
+        assert_eq!(encode_path("a/b"), "a/b");
+        assert_eq!(encode_query("a/b"), "a/b");
+        assert_eq!(encode_form("a/b"), "a%2Fb");
+    }
+
+    #[test]
+    fn test_pair_separators_encoded_in_query_and_form() {
+        for input in ["a&b", "a=b", "a+b", "a#b", "a;b"] {
+            assert!(!encode_query(input).contains(&input[1..2]), "{}", input);
+            assert!(!encode_form(input).contains(&input[1..2]), "{}", input);
+        }
+        assert_eq!(encode_path("a&b=c+d"), "a&b=c+d");
+    }
+
+    #[test]
+    fn test_space_percent_and_non_ascii() {
+        assert_eq!(encode_form("100% off"), "100%25%20off");
+        assert_eq!(encode_path("café"), "caf%C3%A9");
+        assert_eq!(encode_query("日"), "%E6%97%A5");
+    }
+}
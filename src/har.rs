@@ -0,0 +1,211 @@
+//! Reading HAR (HTTP Archive) captures, as used by `--replay`
+//!
+//! Only the parts needed to re-send a request and compare the outcome are
+//! read: each entry's request line, headers and body, and the recorded status
+//! and body text. Timings, cookies and cache details are ignored.
+
+use crate::{CurlError, HttpMethod, RequestConfig};
+use base64::Engine;
+use serde_json::Value;
+
+/// Recorded request headers that aren't sent again: HTTP/2 pseudo-headers and
+/// those the client sets itself. `accept-encoding` goes too, since a browser
+/// may have offered codings bcurl can't decode.
+const SKIPPED_HEADERS: &[&str] = &[
+    "host",
+    "content-length",
+    "connection",
+    "transfer-encoding",
+    "accept-encoding",
+];
+
+/// One recorded request and the response it got
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Method as recorded, which may be one bcurl can't send
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<Vec<u8>>,
+    /// Recorded status; 0 when the browser got no response
+    pub status: u16,
+    /// Recorded response body, if the capture kept it
+    pub response_body: Option<Vec<u8>>,
+}
+
+impl Entry {
+    /// A request repeating this one. Redirects aren't followed, since a
+    /// capture records each hop as an entry of its own.
+    ///
+    /// Fails with [`CurlError::InvalidConfig`] if the method isn't supported.
+    pub fn request(&self) -> Result<RequestConfig, CurlError> {
+        let mut config = RequestConfig::new(&self.url)
+            .method(HttpMethod::try_from(self.method.as_str())?)
+            .follow_redirects(false);
+        for (key, value) in &self.headers {
+            config = config.header(key, value);
+        }
+        if let Some(ref body) = self.body {
+            config = config.body_bytes(body.clone());
+        }
+        Ok(config)
+    }
+}
+
+/// The entries of a HAR file, in recorded order.
+///
+/// Fails with [`CurlError::InvalidConfig`] if `json` isn't a HAR log.
+pub fn parse(json: &str) -> Result<Vec<Entry>, CurlError> {
+    let har: Value = serde_json::from_str(json).map_err(|e| invalid(format!("{}", e)))?;
+    let entries = har["log"]["entries"]
+        .as_array()
+        .ok_or_else(|| invalid("no log.entries array".to_string()))?;
+    entries
+        .iter()
+        .enumerate()
+        .map(|(idx, entry)| {
+            parse_entry(entry).ok_or_else(|| invalid(format!("entry {} is malformed", idx + 1)))
+        })
+        .collect()
+}
+
+fn parse_entry(entry: &Value) -> Option<Entry> {
+    let request = &entry["request"];
+    let mut headers: Vec<(String, String)> = request["headers"]
+        .as_array()
+        .map(|headers| {
+            headers
+                .iter()
+                .filter_map(|header| Some((header["name"].as_str()?, header["value"].as_str()?)))
+                .filter(|(name, _)| {
+                    !name.starts_with(':')
+                        && !SKIPPED_HEADERS.contains(&name.to_ascii_lowercase().as_str())
+                })
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let post_data = &request["postData"];
+    let body = post_data["text"]
+        .as_str()
+        .map(|text| text.as_bytes().to_vec())
+        .or_else(|| {
+            post_data["params"]
+                .as_array()
+                .map(|params| form_body(params))
+        });
+    let has_content_type = headers
+        .iter()
+        .any(|(key, _)| key.eq_ignore_ascii_case("content-type"));
+    if let Some(mime) = post_data["mimeType"].as_str().filter(|_| !has_content_type) {
+        if body.is_some() && !mime.is_empty() {
+            headers.push(("Content-Type".to_string(), mime.to_string()));
+        }
+    }
+
+    let response = &entry["response"];
+    let content = &response["content"];
+    let response_body = content["text"].as_str().map(|text| {
+        if content["encoding"].as_str() == Some("base64") {
+            base64::engine::general_purpose::STANDARD
+                .decode(text)
+                .unwrap_or_else(|_| text.as_bytes().to_vec())
+        } else {
+            text.as_bytes().to_vec()
+        }
+    });
+
+    Some(Entry {
+        method: request["method"].as_str()?.to_ascii_uppercase(),
+        url: request["url"].as_str()?.to_string(),
+        headers,
+        body,
+        status: u16::try_from(response["status"].as_u64()?).ok()?,
+        response_body,
+    })
+}
+
+/// A form body from `postData.params`, which captures use instead of `text`
+/// for some form submissions
+fn form_body(params: &[Value]) -> Vec<u8> {
+    let pairs: Vec<String> = params
+        .iter()
+        .filter_map(|param| {
+            let name = param["name"].as_str()?;
+            let value = param["value"].as_str().unwrap_or_default();
+            Some(format!(
+                "{}={}",
+                crate::encode::encode_form(name),
+                crate::encode::encode_form(value)
+            ))
+        })
+        .collect();
+    pairs.join("&").into_bytes()
+}
+
+fn invalid(reason: String) -> CurlError {
+    CurlError::InvalidConfig(format!("invalid HAR: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPTURE: &str = r#"{"log": {"version": "1.2", "entries": [
+        {"request": {"method": "get", "url": "https://example.com/api",
+            "headers": [
+                {"name": ":authority", "value": "example.com"},
+                {"name": "Accept", "value": "application/json"},
+                {"name": "Accept-Encoding", "value": "gzip, br"}
+            ]},
+         "response": {"status": 200,
+            "content": {"text": "aGk=", "encoding": "base64"}}},
+        {"request": {"method": "POST", "url": "https://example.com/form",
+            "headers": [],
+            "postData": {"mimeType": "application/x-www-form-urlencoded",
+                "params": [{"name": "q", "value": "a b"}, {"name": "n"}]}},
+         "response": {"status": 0, "content": {}}}
+    ]}}"#;
+
+    #[test]
+    fn test_parse() {
+        let entries = parse(CAPTURE).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0].method, "GET");
+        assert_eq!(
+            entries[0].headers,
+            [("Accept".to_string(), "application/json".to_string())]
+        );
+        assert_eq!(entries[0].body, None);
+        assert_eq!(entries[0].status, 200);
+        assert_eq!(entries[0].response_body.as_deref(), Some(&b"hi"[..]));
+
+        assert_eq!(entries[1].body.as_deref(), Some(&b"q=a%20b&n="[..]));
+        assert_eq!(
+            entries[1].headers,
+            [(
+                "Content-Type".to_string(),
+                "application/x-www-form-urlencoded".to_string()
+            )]
+        );
+        assert_eq!(entries[1].status, 0);
+        assert_eq!(entries[1].response_body, None);
+        let request = entries[1].request().unwrap();
+        assert_eq!(request.method, HttpMethod::Post);
+        assert!(!request.follow_redirects);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(parse("not json").is_err());
+        assert!(parse(r#"{"log": {}}"#).is_err());
+        assert!(parse(r#"{"log": {"entries": [{"request": {}}]}}"#).is_err());
+
+        let options = r#"{"log": {"entries": [{"request": {"method": "OPTIONS",
+            "url": "https://example.com/"}, "response": {"status": 204}}]}}"#;
+        let entries = parse(options).unwrap();
+        assert!(entries[0].request().is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Link extraction from HTML, as used by `--list-links`
+//!
+//! This is a scanner, not a parser: it finds `href` attributes on `<a>` tags
+//! and skips comments, which covers directory listings and most simple pages.
+//! Links added by scripts aren't seen.
+
+/// The targets of the `<a href>` links in `html`, resolved against `base`, in
+/// document order without duplicates. Fragment-only links and `javascript:`
+/// URLs are left out, as are hrefs that can't be resolved.
+///
+/// ```
+/// let html = r#"<a href="a.txt">a</a> <A HREF='/b/'>b</A> <a href="a.txt">again</a>"#;
+/// assert_eq!(
+///     bcurl::links::extract(html, "https://example.com/files/"),
+///     ["https://example.com/files/a.txt", "https://example.com/b/"]
+/// );
+/// ```
+pub fn extract(html: &str, base: &str) -> Vec<String> {
+    let base = url::Url::parse(base).ok();
+    let mut links: Vec<String> = Vec::new();
+    for href in hrefs(html) {
+        let href = decode_entities(href.trim());
+        if href.is_empty() || href.starts_with('#') || href.starts_with("javascript:") {
+            continue;
+        }
+        let resolved = match base {
+            Some(ref base) => base.join(&href).map(String::from),
+            None => url::Url::parse(&href).map(String::from),
+        };
+        if let Ok(link) = resolved {
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+    }
+    links
+}
+
+/// The raw `href` values of `<a>` tags, outside comments
+fn hrefs(html: &str) -> Vec<&str> {
+    let lower = html.to_ascii_lowercase();
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find('<') {
+        let start = pos + offset;
+        if lower[start..].starts_with("<!--") {
+            pos = lower[start..]
+                .find("-->")
+                .map_or(lower.len(), |end| start + end + 3);
+            continue;
+        }
+        let end = lower[start..]
+            .find('>')
+            .map_or(lower.len(), |end| start + end);
+        let is_anchor = lower[start + 1..]
+            .starts_with('a')
+            .then(|| lower[start + 2..].chars().next())
+            .flatten()
+            .is_some_and(|c| c.is_ascii_whitespace());
+        if is_anchor {
+            if let Some(href) = attribute(&html[start..end], &lower[start..end], "href") {
+                found.push(href);
+            }
+        }
+        pos = end;
+    }
+    found
+}
+
+/// The value of attribute `name` in a tag, given the tag and its lowercase copy
+fn attribute<'a>(tag: &'a str, lower: &str, name: &str) -> Option<&'a str> {
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find(name) {
+        let at = pos + offset;
+        pos = at + name.len();
+        // Only a whole attribute name, not the end of another one
+        if !lower[..at].ends_with(|c: char| c.is_ascii_whitespace()) {
+            continue;
+        }
+        let rest = lower[pos..].trim_start();
+        let Some(rest) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let value_start = lower.len() - rest.trim_start().len();
+        let value = &tag[value_start..];
+        return Some(match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let value = &value[1..];
+                value.find(quote).map_or(value, |end| &value[..end])
+            }
+            _ => value
+                .find(|c: char| c.is_ascii_whitespace())
+                .map_or(value, |end| &value[..end]),
+        });
+    }
+    None
+}
+
+/// Decode the entities that commonly appear in URLs
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directory_listing() {
+        let html = r##"<html><body><h1>Index of /pub</h1>
+            <!-- <a href="hidden.txt"> -->
+            <a href="../">Parent</a>
+            <a  class="file" href=notes.txt>notes.txt</a>
+            <a href="sub%20dir/">sub dir/</a>
+            <a href="?C=N&amp;O=D">Name</a>
+            <a href="#top">top</a>
+            <abbr href="not-a-link">x</abbr>
+            <a data-href="nope" name="x">anchor</a>
+        </body></html>"##;
+        assert_eq!(
+            extract(html, "http://example.com/pub/"),
+            [
+                "http://example.com/",
+                "http://example.com/pub/notes.txt",
+                "http://example.com/pub/sub%20dir/",
+                "http://example.com/pub/?C=N&O=D",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_absolute_links_kept() {
+        let html = "<a href='https://other.example/x'>x</a><a href=\"mailto:a@b.c\">m</a>";
+        assert_eq!(
+            extract(html, "http://example.com/"),
+            ["https://other.example/x", "mailto:a@b.c"]
+        );
+    }
+}
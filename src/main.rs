@@ -8,16 +8,44 @@
 //! - Automatic compression (gzip/deflate)
 //! - Batch mode for processing URL files
 
-use bcurl::{parse_header, HttpMethod, MinimalCurl, RequestConfig};
+// bcurl::CurlError is large, see the note in lib.rs
+#![allow(clippy::result_large_err)]
+
+use bcurl::{
+    charset, checksum, cookies, diff, generate_trace_id, guess_content_type, parse_header, warc,
+    Algorithm, Checksum, ClientOptions, CookieJar, CurlError, CurlResponse, HttpMethod,
+    MinimalCurl, MultipartPart, RequestConfig,
+};
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::env;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::process::ExitCode;
-use std::sync::Arc;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 const VERSION: &str = "0.3.0";
+
+/// How often parallel mode checks for Ctrl-C while waiting for results
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Set by the Ctrl-C handler; no new requests are started once it is set
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set by SIGUSR1 to ask for a progress report, see [`install_progress_handler`]
+static PROGRESS_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// URLs finished so far in this run, and how many of them failed
+static URLS_DONE: AtomicUsize = AtomicUsize::new(0);
+static URLS_FAILED: AtomicUsize = AtomicUsize::new(0);
+
+/// Output files being written right now, deleted if a second Ctrl-C forces an exit
+static PENDING_OUTPUTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
 const HELP: &str = r#"bcurl - A blazingly fast HTTP client that beats curl for multiple requests
 
 USAGE:
@@ -28,24 +56,159 @@ ARGUMENTS:
 
 OPTIONS:
     -X, --request <METHOD>   HTTP method (GET, POST, PUT, DELETE, HEAD, PATCH) [default: GET]
-    -d, --data <DATA>        Data to send in request body
+    -d, --data <DATA>        Data to send in request body; @FILE reads it from
+                             FILE (@- reads stdin). Repeated -d and
+                             --data-urlencode values are joined with &
+    --data-urlencode <DATA>  Like -d, percent-encoding the data: NAME=VALUE
+                             encodes VALUE, =VALUE or VALUE all of it, and
+                             [NAME]@FILE the file's content. Sets the form
+                             Content-Type unless -H sets one
+    --data-binary <DATA>     Send DATA as-is; @FILE streams the file (@- reads stdin)
+    --auto-content-type      With --data-binary @FILE, set Content-Type from the file
+                             extension unless -H sets one
+    -F, --form <NAME=VALUE>  Add a multipart/form-data field, can be repeated;
+                             NAME=@FILE uploads a file (@- reads stdin), with
+                             optional ;type=MIME and ;filename=NAME suffixes
     -H, --header <HEADER>    Add header (format: "Name: Value"), can be repeated
     -o, --output <FILE>      Write output to file (only for single URL)
     -i, --include            Include response headers in output
     -I, --head               Show only response headers (HEAD request)
+    --status-only            Print only the status code (URL and code per line for
+                             several URLs); add -I to skip the body entirely
+    --output-format <FMT>    Print one line per URL (url, status, elapsed, size)
+                             instead of bodies: text (default, the usual output),
+                             json (one object per line), csv or tsv
+    --expect-status <CODE>   Fail (exit 22) unless every response has status CODE
+    --checksum <ALG:HEX>     Fail unless the body has this digest, e.g. sha256:9f86...
+                             (md5, sha1 and sha256 are supported)
     -L, --location           Follow redirects [default: true]
+    --abort-on-redirect-loop Fail as soon as a redirect revisits a URL in the chain
     -m, --max-time <SECS>    Maximum time for request [default: 30]
+    --connect-timeout <SECS> Maximum time to connect, on its own limit rather than
+                             -m's (fractions allowed) [default: limited by -m]
+    --local-port <LOW-HIGH>  Not supported: ureq opens its own sockets, so the
+                             source port can't be chosen; the option is rejected
+    -Y, --speed-limit <BYTES>
+                             Abort a body arriving at fewer than BYTES per second
+                             over --speed-time [default: 1 with --speed-time]
+    -y, --speed-time <SECS>  Window for --speed-limit (fractions allowed)
+                             [default: 30 with --speed-limit]
+    --job-timeout <SECS>     Maximum time for the whole run (fractions allowed);
+                             unfinished URLs are reported as timed out and bcurl
+                             exits 28
+    --wait <SECS>            Sleep SECS (fractions allowed) between sequential
+                             requests; ignored with --parallel
+    --wait-jitter <SECS>     Add a random extra delay of up to SECS to each --wait
+    -f, --fail               Fail silently (no body) on HTTP errors, exit 22
+    --fail-with-body         Like --fail, but still output the body
+    --etag-save <FILE>       Save the response ETag to FILE
+    --etag-compare <FILE>    Send If-None-Match with the ETag in FILE; a 304 exits 0
+                             with no body
+    -u, --user <USER:PASS>   HTTP Basic auth credentials (no colon: empty password)
+    -b, --cookie <DATA|FILE> Send cookies: "name=value; ..." as given, or read
+                             from a Netscape cookies.txt file, whose cookies then
+                             go only to the sites they belong to
+    -c, --cookie-jar <FILE>  Write the cookies held at the end to FILE in the
+                             Netscape format; with -b FILE, keeps a session
+    -x, --proxy <URL>        Use a proxy, e.g. http://proxy:3128. Without it,
+                             http_proxy, https_proxy, all_proxy and no_proxy
+                             (or their upper case forms) are used
+    --proxy-user <USER:PASS> Credentials for the proxy
+    --proxy-map <HOST=URL>   Send requests for HOST and its subdomains through
+                             the proxy URL instead (repeatable)
+    -p, --proxytunnel        Only go through the proxy in a CONNECT tunnel (HTTPS
+                             always is; plain HTTP URLs are refused)
+    -k, --insecure           Don't verify TLS certificates or host names (for
+                             self-signed test servers only)
+    --input-charset <LABEL>  Decode the body as LABEL instead of the Content-Type charset
+    --output-charset <LABEL> Convert the body to LABEL (e.g. utf-8, shift_jis) for
+                             output; legacy charsets need the `charset` feature
+    --lang <LANGUAGES>       Set Accept-Language (e.g. "de-DE, de;q=0.9")
+    --bearer-cmd <COMMAND>   Run COMMAND and send its output as a Bearer token
+    --trace-id[=<ID>]        Send a correlation ID header (random UUID per request by default)
+                             and show it in --timing, --status-only and --output-format
+    --trace-id-header <NAME> Header used for --trace-id [default: X-Request-Id]
+    --retry <NUM>            Retry transient failures NUM times [default: 0]
+                             (POST and PATCH are not retried)
+    --retry-backoff <SECS>   Wait before the first retry, doubled for each one
+                             after it, up to 60s [default: 1]; a Retry-After
+                             header on a 429 or 503 is honored instead
+    --retry-all-errors       With --retry, retry on every error and non-2xx status
+                             (may repeat non-idempotent requests such as POST)
+    --attempt-timeout <SECS> Time limit for each attempt; -m then limits all
+                             attempts of a request together
     -s, --silent             Silent mode
     -v, --verbose            Verbose output
+    -vv, --trace-pool        Also log whether each request reused a pooled connection
+    --pool-stats             On exit, print how many connections were opened and
+                             reused, per host
+    --percentiles            On exit, print latency percentiles (p50/p90/p99)
+                             over the responses received
+    --hash-bodies            On exit, list the URLs whose bodies were identical
+                             (by SHA-256), to spot mirrors and soft 404s
+    --trace-ascii <FILE>     Dump every request and response, bodies included, to
+                             FILE ("-" for stdout)
+    --trace-max-body <BYTES> Cut bodies in --trace-ascii after BYTES, noting how
+                             much was left out
+    --warc-output <FILE>     Archive every exchange to FILE as WARC 1.1 request
+                             and response records, one file for the whole batch
     -h, --help               Show this help
     -V, --version            Show version
 
 PERFORMANCE OPTIONS (bcurl exclusive):
     -P, --parallel           Execute multiple URLs in parallel (faster!)
-    -B, --batch <FILE>       Read URLs from file (one per line)
+    --parallel-immediate     Like --parallel, but print each result as soon as it
+                             completes
+    --stable-order           With --parallel-immediate, still print in URL order
+    --dashboard              With --parallel, show in-flight requests per host on
+                             the terminal while the batch runs
+    --parallel-max <N>       With --parallel, run at most N requests at once, taking
+                             URLs round-robin across hosts [default: 50]
+    --no-parallel            Always execute sequentially (silences the --parallel hint)
+    --auto-parallel          Go parallel automatically above --parallel-threshold URLs
+    --parallel-threshold <N> URL count above which --parallel is suggested [default: 4]
+    -B, --batch <FILE>       Read URLs from file (one per line); a line may start
+                             with a [label] shown instead of the URL in output
+    --stdin                  Read URLs from stdin and fetch each as its line
+                             arrives, one at a time, until EOF (lines as in -B);
+                             request bodies can't use @- with it
+    --retry-queue <FILE>     Run URLs that failed last time (kept in FILE) first,
+                             then save the ones that fail in this run
+    --parallel-segments <N>  Download a single URL to -o over N connections using
+                             byte ranges (falls back to one if unsupported)
+    --output-concat <FILE>   Write all response bodies, in URL order, into one file
+    --output-tree <DIR>      Save each body under DIR/<host>/<path>, mirroring the
+                             URL; a query string adds a hash to the file name
+    --concat-separator <SEP> Text written between bodies in --output-concat
+                             (\n and \t escapes are recognized)
+    --compressed             Ask for a gzip/deflate response (the default)
     --no-compression         Disable automatic gzip/deflate compression
+    --no-tcp-nodelay         Let the OS batch small writes (Nagle's algorithm);
+                             TCP_NODELAY is on by default, as in curl
+    --raw, --no-decode       Keep a compressed response as sent instead of
+                             decompressing it (--no-compression stops asking
+                             for compression in the first place)
+    --bomb-ratio <N>         With -v, warn when a body decompresses to more than
+                             N times its compressed size (default 100, 0 = off)
+    --list-links             Print the <a href> links in the body, one per line,
+                             resolved against the final URL, instead of the body
+    --hex                    Print the body as a hex dump (done automatically for
+                             binary bodies when stdout is a terminal)
     --timing                 Show timing information for each request
 
+COMPARISON OPTIONS:
+    --diff                   Fetch exactly two URLs and print a unified diff of the
+                             responses; exits 1 if they differ
+    --diff-headers           Include response headers in the comparison
+    --diff-ignore-header <NAME>
+                             Skip a header when comparing, can be repeated
+                             (Date is always ignored)
+    --replay <FILE>          Re-send the requests recorded in a HAR file one by one
+                             and report statuses that differ from the recording;
+                             exits 1 if any differ (needs the `serde` feature)
+    --compare                With --replay, also diff each body against the
+                             recorded one
+
 EXAMPLES:
     # Single request (same as curl)
     bcurl https://httpbin.org/get
@@ -59,9 +222,19 @@ EXAMPLES:
     # Batch mode with parallel execution
     bcurl --batch urls.txt --parallel
 
+    # Compare staging and production
+    bcurl --diff https://staging.example.com/api https://example.com/api
+
+    # Check an API against a browser capture
+    bcurl --replay capture.har --compare
+
     # POST with JSON
     bcurl -X POST -d '{"key":"value"}' -H "Content-Type: application/json" https://httpbin.org/post
 
+SIGNALS:
+    On Unix, send SIGUSR1 (kill -USR1 <pid>) during a batch to print how many
+    URLs are done, failed and remaining, without stopping the run
+
 WHY BCURL IS FASTER:
     - Multiple URLs to same host: 50-80% faster (connection reuse)
     - Parallel requests: Up to Nx faster for N URLs
@@ -70,40 +243,188 @@ WHY BCURL IS FASTER:
 
 struct Args {
     urls: Vec<String>,
+    /// Label for each of `urls`, from the batch file
+    labels: Vec<Option<String>>,
     method: String,
     data: Option<String>,
+    /// --data-urlencode was used, so the body is a form
+    data_urlencoded: bool,
+    data_binary: Option<Vec<u8>>,
+    /// File --data-binary streams the body from
+    data_binary_file: Option<String>,
+    auto_content_type: bool,
+    form: Vec<MultipartPart>,
     headers: Vec<String>,
     output: Option<String>,
     include_headers: bool,
     head_only: bool,
+    status_only: bool,
+    output_format: OutputFormat,
+    expect_status: Option<u16>,
+    checksum: Option<Checksum>,
     follow_redirects: bool,
+    abort_on_redirect_loop: bool,
     timeout: u64,
+    job_timeout: Option<Duration>,
+    attempt_timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    speed_limit: Option<u64>,
+    speed_time: Option<Duration>,
+    retry_backoff: Option<Duration>,
+    wait: Option<Duration>,
+    wait_jitter: Option<Duration>,
     silent: bool,
     verbose: bool,
+    trace_pool: bool,
+    pool_stats: bool,
+    trace_ascii: Option<String>,
+    trace_max_body: Option<usize>,
+    warc_output: Option<String>,
     parallel: bool,
     batch_file: Option<String>,
+    /// Fetch URLs streamed on stdin as they arrive
+    stdin: bool,
+    retry_queue: Option<String>,
     compression: bool,
+    decode_body: bool,
+    bomb_ratio: u32,
+    hex: bool,
+    list_links: bool,
+    hash_bodies: bool,
+    percentiles: bool,
     timing: bool,
+    bearer_cmd: Option<String>,
+    retries: u32,
+    retry_all_errors: bool,
+    fail: bool,
+    fail_with_body: bool,
+    etag_save: Option<String>,
+    proxy: Option<String>,
+    input_charset: Option<String>,
+    output_charset: Option<String>,
+    proxy_user: Option<String>,
+    /// (host, proxy) pairs from --proxy-map
+    proxy_map: Vec<(String, String)>,
+    proxy_tunnel: bool,
+    /// Basic auth credentials from -u, split at the first colon
+    user: Option<(String, Option<String>)>,
+    tcp_nodelay: bool,
+    /// Netscape cookie file from -b
+    cookie_file: Option<String>,
+    /// File -c writes the cookies to
+    cookie_jar: Option<String>,
+    insecure: bool,
+    etag_compare: Option<String>,
+    /// `Some(None)` generates a fresh ID per request, `Some(Some(id))` uses a fixed one
+    trace_id: Option<Option<String>>,
+    trace_id_header: String,
+    diff: bool,
+    diff_headers: bool,
+    diff_ignore_headers: Vec<String>,
+    /// HAR file whose requests --replay re-sends
+    replay: Option<String>,
+    compare: bool,
+    lang: Option<String>,
+    no_parallel: bool,
+    parallel_immediate: bool,
+    parallel_segments: Option<usize>,
+    parallel_max: usize,
+    stable_order: bool,
+    dashboard: bool,
+    auto_parallel: bool,
+    parallel_threshold: usize,
+    output_concat: Option<String>,
+    output_tree: Option<String>,
+    concat_separator: Option<String>,
 }
 
 impl Default for Args {
     fn default() -> Self {
         Self {
             urls: Vec::new(),
+            labels: Vec::new(),
             method: "GET".to_string(),
             data: None,
+            data_urlencoded: false,
+            data_binary: None,
+            data_binary_file: None,
+            auto_content_type: false,
+            form: Vec::new(),
             headers: Vec::new(),
             output: None,
             include_headers: false,
             head_only: false,
+            status_only: false,
+            output_format: OutputFormat::Text,
+            expect_status: None,
+            checksum: None,
             follow_redirects: true,
+            abort_on_redirect_loop: false,
             timeout: 30,
+            job_timeout: None,
+            attempt_timeout: None,
+            connect_timeout: None,
+            speed_limit: None,
+            speed_time: None,
+            retry_backoff: None,
+            wait: None,
+            wait_jitter: None,
             silent: false,
             verbose: false,
+            trace_pool: false,
+            pool_stats: false,
+            trace_ascii: None,
+            trace_max_body: None,
+            warc_output: None,
             parallel: false,
             batch_file: None,
+            stdin: false,
+            retry_queue: None,
             compression: true,
+            decode_body: true,
+            bomb_ratio: 100,
+            hex: false,
+            list_links: false,
+            hash_bodies: false,
+            percentiles: false,
             timing: false,
+            bearer_cmd: None,
+            retries: 0,
+            retry_all_errors: false,
+            fail: false,
+            fail_with_body: false,
+            etag_save: None,
+            proxy: None,
+            input_charset: None,
+            output_charset: None,
+            proxy_user: None,
+            proxy_map: Vec::new(),
+            proxy_tunnel: false,
+            cookie_file: None,
+            cookie_jar: None,
+            user: None,
+            tcp_nodelay: true,
+            insecure: false,
+            etag_compare: None,
+            trace_id: None,
+            trace_id_header: "X-Request-Id".to_string(),
+            diff: false,
+            diff_headers: false,
+            diff_ignore_headers: Vec::new(),
+            replay: None,
+            compare: false,
+            lang: None,
+            no_parallel: false,
+            parallel_immediate: false,
+            parallel_segments: None,
+            parallel_max: 50,
+            stable_order: false,
+            dashboard: false,
+            auto_parallel: false,
+            parallel_threshold: 4,
+            output_concat: None,
+            output_tree: None,
+            concat_separator: None,
         }
     }
 }
@@ -115,6 +436,14 @@ fn parse_args() -> Result<Args, String> {
         return Err("URL is required".to_string());
     }
 
+    // --stdin reads URLs from stdin, so a body can't come from there too.
+    // Checked up front, since `@-` arguments read stdin as they're parsed.
+    if args.iter().any(|arg| arg == "--stdin")
+        && args.windows(2).any(|pair| reads_stdin(&pair[0], &pair[1]))
+    {
+        return Err("--stdin can't be combined with a body read from stdin (@-)".to_string());
+    }
+
     let mut result = Args::default();
     let mut i = 0;
 
@@ -142,7 +471,50 @@ fn parse_args() -> Result<Args, String> {
                 if i >= args.len() {
                     return Err("-d requires a data argument".to_string());
                 }
-                result.data = Some(args[i].clone());
+                append_data(&mut result.data, &data_arg(&args[i])?);
+            }
+            "--data-urlencode" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--data-urlencode requires a data argument".to_string());
+                }
+                let data = urlencode_data(&args[i])?;
+                append_data(&mut result.data, &data);
+                result.data_urlencoded = true;
+            }
+            "--data-binary" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--data-binary requires a data argument".to_string());
+                }
+                result.data_binary_file = None;
+                result.data_binary = match args[i].strip_prefix('@') {
+                    Some("-") => {
+                        let mut bytes = Vec::new();
+                        io::stdin()
+                            .read_to_end(&mut bytes)
+                            .map_err(|e| format!("Failed to read stdin: {}", e))?;
+                        Some(bytes)
+                    }
+                    // Streamed from disk when sent; fail early if it's missing
+                    Some(path) => {
+                        File::open(path)
+                            .map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+                        result.data_binary_file = Some(path.to_string());
+                        None
+                    }
+                    None => Some(args[i].clone().into_bytes()),
+                };
+            }
+            "--auto-content-type" => result.auto_content_type = true,
+            "-F" | "--form" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("-F requires a name=value argument".to_string());
+                }
+                result
+                    .form
+                    .push(MultipartPart::parse(&args[i]).map_err(|e| e.to_string())?);
             }
             "-H" | "--header" => {
                 i += 1;
@@ -151,6 +523,25 @@ fn parse_args() -> Result<Args, String> {
                 }
                 result.headers.push(args[i].clone());
             }
+            "-b" | "--cookie" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("-b requires cookie data or a file name".to_string());
+                }
+                // As in curl, anything with a `=` is cookie data
+                if args[i].contains('=') {
+                    result.headers.push(format!("Cookie: {}", args[i]));
+                } else {
+                    result.cookie_file = Some(args[i].clone());
+                }
+            }
+            "-c" | "--cookie-jar" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("-c requires a file name".to_string());
+                }
+                result.cookie_jar = Some(args[i].clone());
+            }
             "-o" | "--output" => {
                 i += 1;
                 if i >= args.len() {
@@ -174,14 +565,383 @@ fn parse_args() -> Result<Args, String> {
                 }
                 result.batch_file = Some(args[i].clone());
             }
+            "--stdin" => result.stdin = true,
+            "--retry-queue" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--retry-queue requires a file argument".to_string());
+                }
+                result.retry_queue = Some(args[i].clone());
+            }
+            "--bearer-cmd" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--bearer-cmd requires a command argument".to_string());
+                }
+                result.bearer_cmd = Some(args[i].clone());
+            }
+            "--bomb-ratio" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--bomb-ratio requires a ratio argument".to_string());
+                }
+                result.bomb_ratio = args[i]
+                    .parse()
+                    .map_err(|_| format!("Invalid bomb ratio: {}", args[i]))?;
+            }
+            "--retry" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--retry requires a count argument".to_string());
+                }
+                result.retries = args[i]
+                    .parse()
+                    .map_err(|_| format!("Invalid retry count: {}", args[i]))?;
+            }
+            "--trace-id-header" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--trace-id-header requires a header name".to_string());
+                }
+                result.trace_id_header = args[i].clone();
+            }
+            "--diff-ignore-header" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--diff-ignore-header requires a header name".to_string());
+                }
+                result.diff_ignore_headers.push(args[i].to_lowercase());
+            }
+            "--replay" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--replay requires a HAR file argument".to_string());
+                }
+                result.replay = Some(args[i].clone());
+            }
+            "--lang" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--lang requires a language argument".to_string());
+                }
+                result.lang = Some(args[i].clone());
+            }
+            "--parallel-threshold" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--parallel-threshold requires a count argument".to_string());
+                }
+                result.parallel_threshold = args[i]
+                    .parse()
+                    .map_err(|_| format!("Invalid parallel threshold: {}", args[i]))?;
+            }
+            "--output-concat" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--output-concat requires a file argument".to_string());
+                }
+                result.output_concat = Some(args[i].clone());
+            }
+            "--output-tree" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--output-tree requires a directory argument".to_string());
+                }
+                result.output_tree = Some(args[i].clone());
+            }
+            "--parallel-segments" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--parallel-segments requires a count argument".to_string());
+                }
+                let segments = args[i]
+                    .parse()
+                    .ok()
+                    .filter(|&n: &usize| n > 0)
+                    .ok_or_else(|| format!("Invalid segment count: {}", args[i]))?;
+                result.parallel_segments = Some(segments);
+            }
+            "--parallel-max" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--parallel-max requires a count argument".to_string());
+                }
+                result.parallel_max = args[i]
+                    .parse()
+                    .ok()
+                    .filter(|&n: &usize| n > 0)
+                    .ok_or_else(|| format!("Invalid parallel maximum: {}", args[i]))?;
+            }
+            "--expect-status" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--expect-status requires a status code".to_string());
+                }
+                let status = args[i]
+                    .parse()
+                    .ok()
+                    .filter(|n: &u16| (100..1000).contains(n))
+                    .ok_or_else(|| format!("Invalid status code: {}", args[i]))?;
+                result.expect_status = Some(status);
+            }
+            "--trace-ascii" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--trace-ascii requires a file argument".to_string());
+                }
+                result.trace_ascii = Some(args[i].clone());
+            }
+            "--warc-output" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--warc-output requires a file argument".to_string());
+                }
+                result.warc_output = Some(args[i].clone());
+            }
+            "--trace-max-body" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--trace-max-body requires a byte count".to_string());
+                }
+                result.trace_max_body = Some(
+                    args[i]
+                        .parse()
+                        .map_err(|_| format!("Invalid byte count: {}", args[i]))?,
+                );
+            }
+            "--checksum" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--checksum requires an <algorithm>:<hex> argument".to_string());
+                }
+                result.checksum = Some(Checksum::parse(&args[i]).map_err(|e| e.to_string())?);
+            }
+            "--job-timeout" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--job-timeout requires a timeout argument".to_string());
+                }
+                let secs = args[i]
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| format!("Invalid job timeout: {}", args[i]))?;
+                result.job_timeout = Some(secs);
+            }
+            "--retry-backoff" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--retry-backoff requires a number of seconds".to_string());
+                }
+                let secs = args[i]
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| format!("Invalid retry backoff: {}", args[i]))?;
+                result.retry_backoff = Some(secs);
+            }
+            "--connect-timeout" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--connect-timeout requires a number of seconds".to_string());
+                }
+                let secs = args[i]
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| format!("Invalid connect timeout: {}", args[i]))?;
+                result.connect_timeout = Some(secs);
+            }
+            "--local-port" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--local-port requires a port range".to_string());
+                }
+                parse_port_range(&args[i])?;
+                return Err(
+                    "--local-port isn't supported by this build (ureq has no socket connector hook)"
+                        .to_string(),
+                );
+            }
+            "-Y" | "--speed-limit" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--speed-limit requires a number of bytes per second".to_string());
+                }
+                let limit = args[i]
+                    .parse()
+                    .map_err(|_| format!("Invalid speed limit: {}", args[i]))?;
+                result.speed_limit = Some(limit);
+            }
+            "-y" | "--speed-time" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--speed-time requires a number of seconds".to_string());
+                }
+                let secs = args[i]
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .filter(|secs| !secs.is_zero())
+                    .ok_or_else(|| format!("Invalid speed time: {}", args[i]))?;
+                result.speed_time = Some(secs);
+            }
+            "--attempt-timeout" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--attempt-timeout requires a number of seconds".to_string());
+                }
+                let secs = args[i]
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| format!("Invalid attempt timeout: {}", args[i]))?;
+                result.attempt_timeout = Some(secs);
+            }
+            "--wait" | "--wait-jitter" => {
+                let flag = args[i].clone();
+                i += 1;
+                if i >= args.len() {
+                    return Err(format!("{} requires a number of seconds", flag));
+                }
+                let secs = args[i]
+                    .parse()
+                    .ok()
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .ok_or_else(|| format!("Invalid {} delay: {}", flag, args[i]))?;
+                if flag == "--wait" {
+                    result.wait = Some(secs);
+                } else {
+                    result.wait_jitter = Some(secs);
+                }
+            }
+            "-x" | "--proxy" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("-x requires a proxy URL argument".to_string());
+                }
+                result.proxy = Some(args[i].clone());
+            }
+            "-u" | "--user" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("-u requires a user:password argument".to_string());
+                }
+                result.user = Some(match args[i].split_once(':') {
+                    Some((user, password)) => (user.to_string(), Some(password.to_string())),
+                    None => (args[i].clone(), None),
+                });
+            }
+            "-p" | "--proxytunnel" => result.proxy_tunnel = true,
+            "-k" | "--insecure" => result.insecure = true,
+            "--tcp-nodelay" => result.tcp_nodelay = true,
+            "--no-tcp-nodelay" => result.tcp_nodelay = false,
+            "--proxy-user" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--proxy-user requires a user:password argument".to_string());
+                }
+                result.proxy_user = Some(args[i].clone());
+            }
+            "--proxy-map" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--proxy-map requires a host=proxy argument".to_string());
+                }
+                match args[i].split_once('=') {
+                    Some((host, proxy)) if !host.is_empty() && !proxy.is_empty() => {
+                        result.proxy_map.push((host.to_string(), proxy.to_string()));
+                    }
+                    _ => {
+                        return Err(format!(
+                            "Invalid --proxy-map '{}': expected host=proxy",
+                            args[i]
+                        ))
+                    }
+                }
+            }
+            "--input-charset" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--input-charset requires a charset argument".to_string());
+                }
+                result.input_charset = Some(args[i].clone());
+            }
+            "--output-charset" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--output-charset requires a charset argument".to_string());
+                }
+                result.output_charset = Some(args[i].clone());
+            }
+            "--etag-save" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--etag-save requires a file argument".to_string());
+                }
+                result.etag_save = Some(args[i].clone());
+            }
+            "--etag-compare" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--etag-compare requires a file argument".to_string());
+                }
+                result.etag_compare = Some(args[i].clone());
+            }
+            "--concat-separator" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--concat-separator requires a separator argument".to_string());
+                }
+                result.concat_separator = Some(unescape(&args[i]));
+            }
             "-i" | "--include" => result.include_headers = true,
             "-I" | "--head" => result.head_only = true,
+            "--status-only" => result.status_only = true,
+            "--output-format" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err("--output-format requires a format argument".to_string());
+                }
+                result.output_format = OutputFormat::parse(&args[i])?;
+            }
             "-L" | "--location" => result.follow_redirects = true,
+            "--abort-on-redirect-loop" => result.abort_on_redirect_loop = true,
             "-s" | "--silent" => result.silent = true,
             "-v" | "--verbose" => result.verbose = true,
+            "-vv" => {
+                result.verbose = true;
+                result.trace_pool = true;
+            }
+            "--trace-pool" => result.trace_pool = true,
+            "--pool-stats" => result.pool_stats = true,
             "-P" | "--parallel" => result.parallel = true,
+            "--no-parallel" => result.no_parallel = true,
+            "--parallel-immediate" => {
+                result.parallel = true;
+                result.parallel_immediate = true;
+            }
+            "--stable-order" => result.stable_order = true,
+            "--dashboard" => result.dashboard = true,
+            "--auto-parallel" => result.auto_parallel = true,
+            "--compressed" => result.compression = true,
             "--no-compression" => result.compression = false,
+            "--raw" | "--no-decode" => result.decode_body = false,
+            "--hex" => result.hex = true,
+            "--list-links" => result.list_links = true,
+            "--hash-bodies" => result.hash_bodies = true,
+            "--percentiles" => result.percentiles = true,
             "--timing" => result.timing = true,
+            "--retry-all-errors" => result.retry_all_errors = true,
+            "-f" | "--fail" => result.fail = true,
+            "--fail-with-body" => result.fail_with_body = true,
+            "--trace-id" => result.trace_id = Some(None),
+            "--diff" => result.diff = true,
+            "--diff-headers" => result.diff_headers = true,
+            "--compare" => result.compare = true,
+            arg if arg.starts_with("--trace-id=") => {
+                result.trace_id = Some(Some(arg["--trace-id=".len()..].to_string()));
+            }
             arg if arg.starts_with('-') => {
                 return Err(format!("Unknown option: {}", arg));
             }
@@ -194,6 +954,7 @@ fn parse_args() -> Result<Args, String> {
     }
 
     // Load URLs from batch file if specified
+    result.labels = vec![None; result.urls.len()];
     if let Some(ref batch_file) = result.batch_file {
         let file = File::open(batch_file)
             .map_err(|e| format!("Failed to open batch file '{}': {}", batch_file, e))?;
@@ -203,241 +964,1920 @@ fn parse_args() -> Result<Args, String> {
             let line = line.trim();
             // Skip empty lines and comments
             if !line.is_empty() && !line.starts_with('#') {
-                result.urls.push(line.to_string());
+                let (label, url) = parse_batch_line(line)?;
+                result.labels.push(label);
+                result.urls.push(url.to_string());
             }
         }
     }
 
-    if result.urls.is_empty() {
+    // URLs left over from a previous run go first
+    if let Some(ref path) = result.retry_queue {
+        let queued = read_retry_queue(path)
+            .map_err(|e| format!("Failed to read retry queue '{}': {}", path, e))?;
+        let mut urls: Vec<String> = queued
+            .into_iter()
+            .filter(|url| !result.urls.contains(url))
+            .collect();
+        let mut labels = vec![None; urls.len()];
+        labels.append(&mut result.labels);
+        urls.append(&mut result.urls);
+        result.urls = urls;
+        result.labels = labels;
+    }
+
+    if result.replay.is_some() {
+        if !result.urls.is_empty() || result.stdin {
+            return Err("--replay takes its requests from the HAR file, not URLs".to_string());
+        }
+    } else if result.compare {
+        return Err("--compare requires --replay".to_string());
+    } else if result.urls.is_empty() && !result.stdin {
         return Err("At least one URL is required".to_string());
     }
 
+    if result.stdin
+        && (result.output.is_some() || result.diff || result.parallel_segments.is_some())
+    {
+        return Err("--stdin can't be combined with -o, --diff or --parallel-segments".to_string());
+    }
+
+    if result.output_tree.is_some() && (result.output.is_some() || result.output_concat.is_some()) {
+        return Err("--output-tree can't be combined with -o or --output-concat".to_string());
+    }
+
+    if result.parallel_segments.is_some() && (result.urls.len() > 1 || result.output.is_none()) {
+        return Err("--parallel-segments needs a single URL and -o".to_string());
+    }
+
+    if (result.etag_save.is_some() || result.etag_compare.is_some()) && result.urls.len() > 1 {
+        return Err("--etag-save and --etag-compare work with a single URL".to_string());
+    }
+
     Ok(result)
 }
 
-#[inline]
-fn parse_method(method: &str) -> Result<HttpMethod, String> {
-    match method.to_uppercase().as_str() {
-        "GET" => Ok(HttpMethod::Get),
-        "POST" => Ok(HttpMethod::Post),
-        "PUT" => Ok(HttpMethod::Put),
-        "DELETE" => Ok(HttpMethod::Delete),
-        "HEAD" => Ok(HttpMethod::Head),
-        "PATCH" => Ok(HttpMethod::Patch),
-        _ => Err(format!("Unknown HTTP method: {}", method)),
+/// Where --output-tree saves the body of `url`: `dir/<host>/<path>`.
+///
+/// A path ending in `/` gets `index.html`. A query string is hashed into the
+/// file name, before its extension, so `?page=1` and `?page=2` don't collide.
+/// Components are made safe for any file system, and `.`/`..` are dropped.
+fn tree_path(dir: &Path, url: &str) -> Option<PathBuf> {
+    let url = url::Url::parse(url).ok()?;
+    let mut host = url.host_str().unwrap_or("localhost").to_string();
+    if let Some(port) = url.port() {
+        host.push_str(&format!("_{}", port));
     }
-}
+    let mut path = dir.join(sanitize_component(&host));
 
-/// Execute requests sequentially with connection reuse
-fn execute_sequential(
-    client: &MinimalCurl,
-    urls: &[String],
-    args: &Args,
-    method: HttpMethod,
-    headers: &[(String, String)],
-) -> bool {
-    let mut all_success = true;
+    let segments: Vec<&str> = url.path_segments().map_or_else(Vec::new, Iterator::collect);
+    let (last, parents) = segments.split_last().unwrap_or((&"", &[]));
+    for segment in parents {
+        if !matches!(*segment, "" | "." | "..") {
+            path.push(sanitize_component(segment));
+        }
+    }
+    let mut name = match *last {
+        "" | "." | ".." => "index.html".to_string(),
+        last => sanitize_component(last),
+    };
+    if let Some(query) = url.query() {
+        let hash = checksum::to_hex(&Algorithm::Sha256.digest(query.as_bytes()));
+        let suffix = format!("_{}", &hash[..8]);
+        match name.rfind('.').filter(|&dot| dot > 0) {
+            Some(dot) => name.insert_str(dot, &suffix),
+            None => name.push_str(&suffix),
+        }
+    }
+    path.push(name);
+    Some(path)
+}
 
-    for (idx, url) in urls.iter().enumerate() {
-        let start = Instant::now();
+/// Replace characters that aren't allowed in file names on some systems, and
+/// keep the name to a length every file system accepts
+fn sanitize_component(component: &str) -> String {
+    let mut safe: String = component
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if safe.len() > 200 {
+        let mut end = 200;
+        while !safe.is_char_boundary(end) {
+            end -= 1;
+        }
+        safe.truncate(end);
+    }
+    safe
+}
 
-        // Build request configuration
-        let mut config = RequestConfig::new(url)
-            .method(method)
-            .follow_redirects(args.follow_redirects)
-            .verbose(args.verbose)
-            .include_headers(args.include_headers)
-            .compression(args.compression)
-            .timeout(Duration::from_secs(args.timeout));
+/// Split a batch file line into its optional `[label]` and the URL
+fn parse_batch_line(line: &str) -> Result<(Option<String>, &str), String> {
+    let Some(rest) = line.strip_prefix('[') else {
+        return Ok((None, line));
+    };
+    let (label, url) = rest
+        .split_once(']')
+        .ok_or_else(|| format!("Unterminated label in batch file line: {}", line))?;
+    Ok((Some(label.trim().to_string()), url.trim()))
+}
 
-        // Add data if provided
-        if let Some(ref data) = args.data {
-            config = config.data(data.clone());
+/// Expand the `\n`, `\t` and `\\` escapes, which are awkward to type in most shells
+fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
         }
-
-        // Add output file if specified (only for single URL)
-        if urls.len() == 1 {
-            if let Some(ref output) = args.output {
-                config = config.output_file(output);
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
             }
+            None => result.push('\\'),
         }
+    }
+    result
+}
 
-        // Add headers
-        for (key, value) in headers {
-            config = config.header(key.clone(), value.clone());
-        }
+/// A --local-port range, `LOW-HIGH` or a single port as in curl
+fn parse_port_range(value: &str) -> Result<(u16, u16), String> {
+    let invalid = || format!("Invalid local port range: {}", value);
+    let (low, high) = value.split_once('-').unwrap_or((value, value));
+    let low: u16 = low.trim().parse().map_err(|_| invalid())?;
+    let high: u16 = high.trim().parse().map_err(|_| invalid())?;
+    if low == 0 || low > high {
+        return Err(invalid());
+    }
+    Ok((low, high))
+}
 
-        match client.execute(&config) {
-            Ok(response) => {
-                let elapsed = start.elapsed();
+/// The body given by a -d argument: the content of `@FILE` (stdin for `@-`),
+/// otherwise the argument itself
+fn data_arg(value: &str) -> Result<String, String> {
+    match value.strip_prefix('@') {
+        Some(path) => read_data_file(path),
+        None => Ok(value.to_string()),
+    }
+}
 
-                // Print headers if requested
-                if args.include_headers || args.head_only {
-                    if urls.len() > 1 {
-                        println!("=== {} ===", url);
-                    }
-                    println!("HTTP/1.1 {} {}", response.status, response.status_text);
-                    for (key, value) in &response.headers {
-                        println!("{}: {}", key, value);
-                    }
-                    println!();
-                }
+/// The content of a -d or --data-urlencode `@FILE`, or of stdin for `@-`
+fn read_data_file(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut data = String::new();
+        io::stdin()
+            .read_to_string(&mut data)
+            .map_err(|e| format!("Failed to read stdin: {}", e))?;
+        return Ok(data);
+    }
+    std::fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))
+}
 
-                // Print body (unless head-only or output to file)
-                if !args.head_only && (urls.len() == 1 || args.output.is_none()) {
-                    if urls.len() > 1 && !args.include_headers {
-                        println!("=== {} ===", url);
-                    }
-                    print!("{}", response.body);
-                    if urls.len() > 1 {
-                        println!(); // Add newline between responses
-                    }
+/// Whether option `flag` with argument `value` takes a body from stdin
+fn reads_stdin(flag: &str, value: &str) -> bool {
+    match flag {
+        "-d" | "--data" | "--data-binary" => value == "@-",
+        "--data-urlencode" => value
+            .find(['=', '@'])
+            .is_some_and(|at| &value[at..] == "@-"),
+        "-F" | "--form" => value
+            .split_once('=')
+            .is_some_and(|(_, value)| value.split(';').next() == Some("@-")),
+        _ => false,
+    }
+}
+
+/// Add `data` to the request body, joined to earlier -d values with `&` as curl does
+fn append_data(body: &mut Option<String>, data: &str) {
+    match body {
+        Some(body) => {
+            body.push('&');
+            body.push_str(data);
+        }
+        None => *body = Some(data.to_string()),
+    }
+}
+
+/// Encode a --data-urlencode argument the way curl does: `name=value` encodes
+/// the value, `=value` and a plain `value` encode all of it, and
+/// `name@file` encodes the file's content
+fn urlencode_data(spec: &str) -> Result<String, String> {
+    let (name, content) = match spec.find(['=', '@']) {
+        Some(at) if spec[at..].starts_with('@') => {
+            (&spec[..at], Cow::Owned(read_data_file(&spec[at + 1..])?))
+        }
+        Some(at) => (&spec[..at], Cow::Borrowed(&spec[at + 1..])),
+        None => ("", Cow::Borrowed(spec)),
+    };
+    let encoded = bcurl::encode::encode_form(&content);
+    Ok(if name.is_empty() {
+        encoded
+    } else {
+        format!("{}={}", name, encoded)
+    })
+}
+
+/// Run an external credential helper and return the token it prints on stdout
+fn run_token_command(command: &str) -> Result<String, String> {
+    // Run through the platform shell so helpers with arguments work as typed
+    let output = if cfg!(windows) {
+        Command::new("cmd").args(["/C", command]).output()
+    } else {
+        Command::new("sh").args(["-c", command]).output()
+    }
+    .map_err(|e| format!("Failed to run token command '{}': {}", command, e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut message = format!("Token command '{}' failed with {}", command, output.status);
+        if !stderr.trim().is_empty() {
+            message.push_str(": ");
+            message.push_str(stderr.trim());
+        }
+        return Err(message);
+    }
+
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if token.is_empty() {
+        return Err(format!("Token command '{}' produced no output", command));
+    }
+    Ok(token)
+}
+
+/// Collects response bodies into a single file, in URL order, for --output-concat
+struct ConcatWriter {
+    file: File,
+    separator: Option<String>,
+    written: usize,
+}
+
+impl ConcatWriter {
+    fn create(path: &str, separator: Option<String>) -> io::Result<Self> {
+        Ok(Self {
+            file: File::create(path)?,
+            separator,
+            written: 0,
+        })
+    }
+
+    /// Append one body, preceded by the separator if it isn't the first
+    fn append(&mut self, body: &[u8]) -> io::Result<()> {
+        if self.written > 0 {
+            if let Some(ref separator) = self.separator {
+                self.file.write_all(separator.as_bytes())?;
+            }
+        }
+        self.file.write_all(body)?;
+        self.written += 1;
+        Ok(())
+    }
+}
+
+/// Build the request configuration for a single URL from the parsed arguments
+fn build_config(
+    idx: usize,
+    args: &Args,
+    method: HttpMethod,
+    headers: &[(String, String)],
+) -> RequestConfig {
+    let mut config = RequestConfig::new(&args.urls[idx])
+        .method(method)
+        .follow_redirects(args.follow_redirects)
+        .abort_on_redirect_loop(args.abort_on_redirect_loop)
+        .verbose(args.verbose)
+        .trace_pool(args.trace_pool)
+        .include_headers(args.include_headers)
+        .compression(args.compression)
+        .decode_body(args.decode_body)
+        .compression_warn_ratio(args.bomb_ratio)
+        .timeout(Duration::from_secs(args.timeout))
+        .retries(args.retries)
+        .retry_all_errors(args.retry_all_errors)
+        .fail_on_error(args.fail || args.fail_with_body)
+        // These read the body after it's saved, so it can't just be streamed
+        .keep_body(
+            args.warc_output.is_some()
+                || args.output_concat.is_some()
+                || args.hash_bodies
+                || args.list_links,
+        )
+        // Only the status and size are printed, so there's no need to keep it
+        .discard_body(
+            summary_only(args)
+                && args.output.is_none()
+                && args.warc_output.is_none()
+                && args.output_concat.is_none()
+                && args.trace_ascii.is_none()
+                && !args.hash_bodies
+                && !args.list_links
+                && !args.diff,
+        );
+
+    if let Some(status) = args.expect_status {
+        config = config.expect_status(status);
+    }
+    if let Some(timeout) = args.attempt_timeout {
+        config = config.attempt_timeout(timeout);
+    }
+    if let Some(timeout) = args.connect_timeout {
+        config = config.connect_timeout(timeout);
+    }
+    // As in curl, either option turns the check on with a default for the other
+    if args.speed_limit.is_some() || args.speed_time.is_some() {
+        config = config.speed_limit(
+            args.speed_limit.unwrap_or(1),
+            args.speed_time.unwrap_or(Duration::from_secs(30)),
+        );
+    }
+    if let Some(backoff) = args.retry_backoff {
+        config = config.retry_backoff(backoff);
+    }
+    config.checksum = args.checksum.clone();
+    config.label = args.labels[idx].clone();
+
+    // Add data if provided
+    if let Some(ref data) = args.data {
+        config = config.data(data.clone());
+    }
+    if let Some(ref bytes) = args.data_binary {
+        config = config.body_bytes(bytes.clone());
+    }
+    if let Some(ref path) = args.data_binary_file {
+        config = config.body_file(path.clone());
+    }
+
+    if let Some(ref dir) = args.output_tree {
+        if let Some(path) = tree_path(Path::new(dir), &args.urls[idx]) {
+            // A failure here surfaces when the body is written
+            if let Some(parent) = path.parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            config = config.output_file(path.to_string_lossy());
+        }
+    }
+    if !args.form.is_empty() {
+        config = config.multipart(args.form.clone());
+    }
+
+    // Add headers
+    for (key, value) in headers {
+        config = config.header(key.clone(), value.clone());
+    }
+
+    if let Some(ref lang) = args.lang {
+        config = config.accept_language(lang.clone());
+    }
+
+    if let Some((ref user, ref password)) = args.user {
+        config = config.basic_auth(user.clone(), password.clone());
+    }
+
+    if let Some(ref label) = args.input_charset {
+        config = config.input_charset(label.clone());
+    }
+    if let Some(ref label) = args.output_charset {
+        config = config.output_charset(label.clone());
+    }
+
+    // Each request gets its own correlation ID unless a fixed one was given
+    if let Some(ref trace_id) = args.trace_id {
+        let value = trace_id.clone().unwrap_or_else(generate_trace_id);
+        config = config.trace_id(args.trace_id_header.clone(), value);
+    }
+
+    config
+}
+
+/// Warn when the body is shorter or longer than its Content-Length declared.
+/// Returns true if a mismatch was found.
+fn warn_if_truncated(config: &RequestConfig, response: &CurlResponse, silent: bool) -> bool {
+    if config.method == HttpMethod::Head {
+        return false;
+    }
+    match response.content_length_mismatch() {
+        Some((declared, actual)) => {
+            if !silent {
+                eprintln!(
+                    "Warning: {} declared Content-Length {} but {} bytes were received (truncated?)",
+                    config.url, declared, actual
+                );
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Read the ETag saved by a previous --etag-save run. A missing or empty file
+/// means there is nothing to compare against yet, so the fetch is unconditional.
+fn read_etag(path: &str) -> io::Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => {
+            let etag = contents.trim();
+            Ok((!etag.is_empty()).then(|| etag.to_string()))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Store the response's ETag for a later --etag-compare. Nothing is written if
+/// the server didn't send one, so a 304 keeps the previously saved value.
+fn save_etag(path: &str, response: &CurlResponse) -> io::Result<()> {
+    match response.get_header("etag") {
+        Some(etag) => std::fs::write(path, format!("{}\n", etag)),
+        None => Ok(()),
+    }
+}
+
+/// Read the URLs queued by an earlier --retry-queue run; no file means none
+fn read_retry_queue(path: &str) -> io::Result<Vec<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replace the retry queue with the URLs that didn't complete successfully,
+/// in run order. An empty queue removes the file.
+fn save_retry_queue(path: &str, urls: &[String], outcome: &BatchOutcome) -> io::Result<()> {
+    let pending: Vec<&str> = urls
+        .iter()
+        .filter(|url| {
+            outcome.failed.contains(url)
+                || outcome.timed_out.contains(url)
+                || outcome.cancelled.contains(url)
+        })
+        .map(String::as_str)
+        .collect();
+    if pending.is_empty() {
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    } else {
+        std::fs::write(path, pending.join("\n") + "\n")
+    }
+}
+
+/// The body as bytes in the --output-charset (UTF-8 by default)
+fn output_bytes<'a>(body: &'a str, args: &Args) -> Cow<'a, [u8]> {
+    match args.output_charset {
+        // The label was validated along with the request, so this can't fail
+        Some(ref label) => {
+            charset::encode(body, label).map_or(Cow::Borrowed(body.as_bytes()), Cow::Owned)
+        }
+        None => Cow::Borrowed(body.as_bytes()),
+    }
+}
+
+/// Print a body to stdout in the --output-charset
+fn print_body(body: &str, args: &Args) {
+    let _ = io::stdout().write_all(&output_bytes(body, args));
+}
+
+/// The bytes to output for a response: the text when it was converted from
+/// another charset or is wanted in the --output-charset, otherwise the body as
+/// received (still compressed with --raw)
+fn response_bytes<'a>(response: &'a CurlResponse, args: &Args) -> Cow<'a, [u8]> {
+    let transcoded = args
+        .input_charset
+        .as_deref()
+        .or(response.charset())
+        .is_some_and(charset::converts);
+    if args.decode_body && (transcoded || args.output_charset.is_some()) {
+        output_bytes(&response.body, args)
+    } else {
+        Cow::Borrowed(&response.body_bytes)
+    }
+}
+
+/// Print a response body to stdout, as a hex dump with --hex or when a binary
+/// body would otherwise garble the terminal
+fn print_response_body(response: &CurlResponse, args: &Args) {
+    let mut stdout = io::stdout().lock();
+    if args.list_links {
+        let links = bcurl::links::extract(response.text(), &response.final_url);
+        let _ = stdout.write_all(links.join("\n").as_bytes());
+        return;
+    }
+    let bytes = response_bytes(response, args);
+    let binary = std::str::from_utf8(&bytes).is_err();
+    if args.hex || (binary && stdout.is_terminal()) {
+        let _ = stdout.write_all(hex_dump(&bytes).as_bytes());
+    } else {
+        let _ = stdout.write_all(&bytes);
+    }
+}
+
+/// Render `bytes` like `hexdump -C`: offset, 16 bytes in hex, then as ASCII
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 4 + 16);
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        out.push_str(&format!("{:08x} ", row * 16));
+        for i in 0..16 {
+            if i % 8 == 0 {
+                out.push(' ');
+            }
+            match chunk.get(i) {
+                Some(b) => out.push_str(&format!("{:02x} ", b)),
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str(" |");
+        out.extend(chunk.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        out.push_str("|\n");
+    }
+    out.push_str(&format!("{:08x}\n", bytes.len()));
+    out
+}
+
+/// Report a failed request. With --fail-with-body the body of an HTTP error
+/// is still printed (or saved to the output file).
+fn report_error(config: &RequestConfig, error: &CurlError, args: &Args) {
+    if let CurlError::HttpStatus { ref body, .. } = error {
+        if args.fail_with_body && !args.head_only && !summary_only(args) {
+            match config.output_file {
+                Some(ref path) => {
+                    if let Err(e) = std::fs::write(path, body) {
+                        if !args.silent {
+                            eprintln!("Error: Failed to write '{}': {}", path, e);
+                        }
+                    }
                 }
+                None => print_body(body, args),
+            }
+        }
+    }
+    if !args.silent {
+        eprintln!("Error fetching {}: {}", config.url, error);
+    }
+}
+
+/// How per-URL results are printed, see --output-format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+    Tsv,
+}
+
+impl OutputFormat {
+    fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "tsv" => Ok(Self::Tsv),
+            _ => Err(format!(
+                "Unknown output format '{}' (expected text, json, csv or tsv)",
+                name
+            )),
+        }
+    }
+
+    fn delimiter(self) -> Option<char> {
+        match self {
+            Self::Csv => Some(','),
+            Self::Tsv => Some('\t'),
+            Self::Text | Self::Json => None,
+        }
+    }
+
+    /// Column names, printed once before the first CSV or TSV record
+    fn header(self) -> Option<String> {
+        let delimiter = self.delimiter()?;
+        Some(
+            [
+                "url",
+                "status",
+                "elapsed_ms",
+                "size",
+                "error",
+                "label",
+                "trace_id",
+            ]
+            .join(&delimiter.to_string()),
+        )
+    }
+
+    /// One result for `config`'s URL. A request that failed has no elapsed time
+    /// and an error.
+    fn record(
+        self,
+        config: &RequestConfig,
+        status: u16,
+        elapsed: Option<Duration>,
+        size: usize,
+        error: Option<&str>,
+    ) -> String {
+        let (url, label) = (config.url.as_str(), config.label.as_deref());
+        let trace_id = config.trace_id.as_ref().map(|(_, value)| value.as_str());
+        let elapsed_ms = elapsed.map(|elapsed| format!("{:.2}", elapsed.as_secs_f64() * 1000.0));
+        match self.delimiter() {
+            Some(delimiter) => [
+                url,
+                &status.to_string(),
+                elapsed_ms.as_deref().unwrap_or_default(),
+                &size.to_string(),
+                error.unwrap_or_default(),
+                label.unwrap_or_default(),
+                trace_id.unwrap_or_default(),
+            ]
+            .iter()
+            .map(|field| quote_field(field, delimiter))
+            .collect::<Vec<_>>()
+            .join(&delimiter.to_string()),
+            None => {
+                let mut json = format!(
+                    "{{\"url\":{},\"status\":{},\"elapsed_ms\":{},\"size\":{}",
+                    json_string(url),
+                    status,
+                    elapsed_ms.as_deref().unwrap_or("null"),
+                    size
+                );
+                if let Some(error) = error {
+                    json.push_str(&format!(",\"error\":{}", json_string(error)));
+                }
+                if let Some(label) = label {
+                    json.push_str(&format!(",\"label\":{}", json_string(label)));
+                }
+                if let Some(trace_id) = trace_id {
+                    json.push_str(&format!(",\"trace_id\":{}", json_string(trace_id)));
+                }
+                json.push('}');
+                json
+            }
+        }
+    }
+}
+
+/// Quote a CSV/TSV field if it holds the delimiter, a quote or a line break
+fn quote_field(field: &str, delimiter: char) -> Cow<'_, str> {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// `text` as a JSON string literal
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Whether each URL gets a one-line summary instead of its headers and body
+fn summary_only(args: &Args) -> bool {
+    args.status_only || args.output_format != OutputFormat::Text
+}
+
+/// Print the one-line result for --status-only or --output-format
+fn print_summary(
+    config: &RequestConfig,
+    result: Result<&CurlResponse, &CurlError>,
+    args: &Args,
+    batch: bool,
+) {
+    if args.output_format != OutputFormat::Text {
+        let line = match result {
+            Ok(response) => args.output_format.record(
+                config,
+                response.status,
+                Some(response.elapsed),
+                response
+                    .streamed_to_file
+                    .or(response.discarded)
+                    .map_or(response.body_bytes.len(), |size| size as usize),
+                None,
+            ),
+            Err(e) => {
+                args.output_format
+                    .record(config, error_status(e), None, 0, Some(&e.to_string()))
+            }
+        };
+        println!("{}", line);
+    } else if args.status_only {
+        print_status(
+            config.display_name(),
+            result.map_or_else(error_status, |r| r.status),
+            config.trace_id.as_ref().map(|(_, value)| value.as_str()),
+            batch,
+        );
+    }
+}
+
+/// Print the line used by --status-only, followed by the --trace-id if any.
+/// Requests that got no response at all show `000`, as curl's `%{http_code}`
+/// does.
+fn print_status(url: &str, status: u16, trace_id: Option<&str>, batch: bool) {
+    let trace = trace_id.map(|id| format!(" {}", id)).unwrap_or_default();
+    if batch {
+        println!("{} {:03}{}", url, status, trace);
+    } else {
+        println!("{:03}{}", status, trace);
+    }
+}
+
+/// The status behind a failed request, or 0 if no response arrived
+fn error_status(error: &CurlError) -> u16 {
+    match error {
+        CurlError::HttpStatus { status, .. } => *status,
+        CurlError::UnexpectedStatus { actual, .. } => *actual,
+        _ => 0,
+    }
+}
+
+/// Print the per-request timing line used by --timing
+fn print_timing(idx: usize, config: &RequestConfig, response: &CurlResponse) {
+    let trace = match config.trace_id {
+        Some((ref name, ref value)) => format!(" [{}: {}]", name, value),
+        None => String::new(),
+    };
+    eprintln!(
+        "[{}] {} - {} {} - {:.2}ms{}",
+        idx + 1,
+        config.display_name(),
+        response.status,
+        response.status_text,
+        response.elapsed.as_secs_f64() * 1000.0,
+        trace
+    );
+}
+
+/// Append one request and its response (or error) to the --trace-ascii file,
+/// in curl's `--trace-ascii` layout
+fn write_trace(args: &Args, config: &RequestConfig, result: &Result<CurlResponse, CurlError>) {
+    let Some(ref path) = args.trace_ascii else {
+        return;
+    };
+    let mut out = Vec::new();
+
+    let mut head = format!("{} {}\n", config.method, config.url);
+    for (key, value) in config.headers.iter().chain(&config.trace_id) {
+        head.push_str(&format!("{}: {}\n", key, value));
+    }
+    trace_block(&mut out, "=> Send header", head.as_bytes(), None);
+    if let Some(ref parts) = config.multipart {
+        let _ = writeln!(
+            out,
+            "== Info: multipart/form-data body with {} part(s)",
+            parts.len()
+        );
+    } else if let Some(body) = config
+        .body_bytes
+        .as_deref()
+        .or(config.data.as_deref().map(str::as_bytes))
+    {
+        trace_block(&mut out, "=> Send data", body, args.trace_max_body);
+    } else if let Some(ref path) = config.body_file {
+        let _ = writeln!(out, "== Info: body streamed from file {}", path);
+    }
+
+    match result {
+        Ok(response) => {
+            let mut head = format!("HTTP/1.1 {} {}\n", response.status, response.status_text);
+            for (key, value) in &response.headers {
+                head.push_str(&format!("{}: {}\n", key, value));
+            }
+            trace_block(&mut out, "<= Recv header", head.as_bytes(), None);
+            if let Some(size) = response.streamed_to_file {
+                let _ = writeln!(out, "== Info: {} byte body saved to file", size);
+            } else if !response.body_bytes.is_empty() {
+                trace_block(
+                    &mut out,
+                    "<= Recv data",
+                    &response.body_bytes,
+                    args.trace_max_body,
+                );
+            }
+        }
+        Err(e) => {
+            let _ = writeln!(out, "== Info: {}", e);
+        }
+    }
+
+    let written = if path == "-" {
+        io::stdout().lock().write_all(&out)
+    } else {
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(&out))
+    };
+    if let Err(e) = written {
+        if !args.silent {
+            eprintln!("Error: Failed to write trace '{}': {}", path, e);
+        }
+    }
+}
+
+/// Append the request and response records of one exchange to the
+/// --warc-output file. Failed requests have no response to archive.
+fn write_warc(args: &Args, config: &RequestConfig, result: &Result<CurlResponse, CurlError>) {
+    let (Some(ref path), Ok(response)) = (&args.warc_output, result) else {
+        return;
+    };
+    let started = SystemTime::now() - response.elapsed;
+    let written = std::fs::OpenOptions::new()
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(&warc::exchange(config, response, started)));
+    if let Err(e) = written {
+        if !args.silent {
+            eprintln!("Error: Failed to write WARC '{}': {}", path, e);
+        }
+    }
+}
+
+/// Write `data` as offset-prefixed lines of at most 64 bytes, with anything
+/// unprintable shown as `.`, keeping only the first `limit` bytes
+fn trace_block(out: &mut Vec<u8>, label: &str, data: &[u8], limit: Option<usize>) {
+    let _ = writeln!(out, "{}, {} bytes (0x{:x})", label, data.len(), data.len());
+    let shown = &data[..limit.unwrap_or(data.len()).min(data.len())];
+
+    let mut offset = 0;
+    while offset < shown.len() {
+        let rest = &shown[offset..];
+        let mut end = rest.len().min(64);
+        if let Some(newline) = rest[..end].iter().position(|&b| b == b'\n') {
+            end = newline + 1;
+        }
+        let line: String = rest[..end]
+            .iter()
+            .filter(|&&b| b != b'\r' && b != b'\n')
+            .map(|&b| {
+                if (0x20..0x7f).contains(&b) {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let _ = writeln!(out, "{:04x}: {}", offset, line);
+        offset += end;
+    }
+    if shown.len() < data.len() {
+        let _ = writeln!(out, "[truncated {} bytes]", data.len() - shown.len());
+    }
+}
+
+/// Write the client's cookies to the -c file, if one was given
+fn save_cookie_jar(client: &MinimalCurl, args: &Args) {
+    let (Some(path), Some(jar)) = (args.cookie_jar.as_deref(), client.cookies()) else {
+        return;
+    };
+    if let Err(e) = cookies::save_cookies(path, &jar) {
+        if !args.silent {
+            eprintln!("Error: Failed to write cookies to '{}': {}", path, e);
+        }
+    }
+}
+
+/// Print the connection counts for --pool-stats
+fn print_pool_stats(client: &MinimalCurl, args: &Args) {
+    if !args.pool_stats {
+        return;
+    }
+    let stats = client.pool_stats();
+    eprintln!(
+        "* Connections: {} opened, {} reused",
+        stats.opened(),
+        stats.reused()
+    );
+    for (host, counts) in &stats.hosts {
+        eprintln!(
+            "*   {}: {} opened, {} reused",
+            host, counts.opened, counts.reused
+        );
+    }
+}
+
+/// The --hash-bodies digest of a response body
+fn hash_body(response: &CurlResponse) -> String {
+    checksum::to_hex(&Algorithm::Sha256.digest(response.bytes()))
+}
+
+/// Report the groups of URLs that returned identical bodies, for --hash-bodies
+fn print_duplicate_bodies(outcome: &BatchOutcome, args: &Args) {
+    if !args.hash_bodies || args.silent {
+        return;
+    }
+    let mut groups: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (idx, hash) in &outcome.body_hashes {
+        groups.entry(hash).or_default().push(*idx);
+    }
+    let mut duplicates: Vec<_> = groups
+        .into_iter()
+        .filter(|(_, indexes)| indexes.len() > 1)
+        .collect();
+    if duplicates.is_empty() {
+        eprintln!(
+            "* No identical bodies among {} responses",
+            outcome.body_hashes.len()
+        );
+        return;
+    }
+    for (_, indexes) in &mut duplicates {
+        indexes.sort_unstable();
+    }
+    duplicates.sort_by_key(|(_, indexes)| indexes[0]);
+    for (hash, indexes) in duplicates {
+        eprintln!("* Identical bodies (sha256:{}):", hash);
+        for idx in indexes {
+            eprintln!(
+                "*   {}",
+                args.labels[idx].as_ref().unwrap_or(&args.urls[idx])
+            );
+        }
+    }
+}
+
+/// The `p`th percentile of `sorted`, by nearest rank
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Report the spread of response times, for --percentiles
+fn print_percentiles(outcome: &BatchOutcome, args: &Args) {
+    if !args.percentiles || args.silent || outcome.latencies.is_empty() {
+        return;
+    }
+    let mut sorted = outcome.latencies.clone();
+    sorted.sort_unstable();
+    let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+    eprintln!(
+        "* Latency over {} responses: min {:.2} ms, p50 {:.2} ms, p90 {:.2} ms, p99 {:.2} ms, max {:.2} ms",
+        sorted.len(),
+        ms(sorted[0]),
+        ms(percentile(&sorted, 50)),
+        ms(percentile(&sorted, 90)),
+        ms(percentile(&sorted, 99)),
+        ms(sorted[sorted.len() - 1])
+    );
+}
+
+/// Outcome of running a batch of URLs
+#[derive(Default)]
+struct BatchOutcome {
+    all_success: bool,
+    /// URLs whose request failed or returned an error status
+    failed: Vec<String>,
+    /// URLs that didn't finish before --job-timeout expired
+    timed_out: Vec<String>,
+    /// URLs skipped or abandoned after Ctrl-C
+    cancelled: Vec<String>,
+    /// SHA-256 of each response body, by URL index, with --hash-bodies
+    body_hashes: Vec<(usize, String)>,
+    /// Time taken by each response received, with --percentiles
+    latencies: Vec<Duration>,
+}
+
+/// Sleep for `--wait` plus up to `--wait-jitter`, waking early on Ctrl-C or
+/// when the job deadline passes
+fn wait_between(args: &Args, deadline: Option<Instant>) {
+    let jitter = args.wait_jitter.map_or(Duration::ZERO, |jitter| {
+        let fraction = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        jitter.mul_f64(fraction)
+    });
+    let mut until = Instant::now() + args.wait.unwrap_or_default() + jitter;
+    if let Some(deadline) = deadline {
+        until = until.min(deadline);
+    }
+    while !interrupted() {
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        thread::sleep(left.min(Duration::from_millis(100)));
+    }
+}
+
+/// Count a finished URL for the SIGUSR1 progress report
+fn record_done(failed: bool) {
+    URLS_DONE.fetch_add(1, Ordering::Relaxed);
+    if failed {
+        URLS_FAILED.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Print a one-line progress summary to stderr. It's printed even with
+/// --silent, since it was asked for. `total` is `None` for --stdin, where
+/// more URLs may still arrive.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn report_progress(total: Option<usize>) {
+    let done = URLS_DONE.load(Ordering::Relaxed);
+    let failed = URLS_FAILED.load(Ordering::Relaxed);
+    match total {
+        Some(total) => eprintln!(
+            "* Progress: {} of {} URLs done ({} failed), {} remaining",
+            done,
+            total,
+            failed,
+            total.saturating_sub(done)
+        ),
+        None => eprintln!("* Progress: {} URLs done ({} failed)", done, failed),
+    }
+}
+
+/// Install a SIGUSR1 handler, and a thread that prints a progress report each
+/// time it fires, so one arrives even during a long transfer
+#[cfg(unix)]
+fn install_progress_handler(total: Option<usize>) {
+    extern "C" fn on_sigusr1(_: libc::c_int) {
+        PROGRESS_REQUESTED.store(true, Ordering::SeqCst);
+    }
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    // SA_RESTART keeps the signal from failing a read in progress.
+    let installed = unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigusr1 as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut()) == 0
+    };
+    if installed {
+        thread::spawn(move || loop {
+            thread::sleep(POLL_INTERVAL);
+            if PROGRESS_REQUESTED.swap(false, Ordering::SeqCst) {
+                report_progress(total);
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+fn install_progress_handler(_total: Option<usize>) {}
+
+fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Install the Ctrl-C handler. The first Ctrl-C stops new requests from
+/// starting and lets the run wind down with a summary; a second one exits
+/// at once after removing partially written output files.
+fn install_interrupt_handler(silent: bool) {
+    let result = ctrlc::set_handler(move || {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            let pending = PENDING_OUTPUTS.lock().unwrap_or_else(|e| e.into_inner());
+            // Bodies are saved as <path>.part until they're complete
+            for path in pending.iter() {
+                let _ = std::fs::remove_file(format!("{}.part", path));
+            }
+            std::process::exit(130);
+        }
+        if !silent {
+            eprintln!("\nInterrupted, stopping (press Ctrl-C again to quit immediately)");
+        }
+    });
+    if let Err(e) = result {
+        if !silent {
+            eprintln!("Warning: failed to install Ctrl-C handler: {}", e);
+        }
+    }
+}
+
+/// Marks an output file as being written for as long as the guard lives
+struct PendingOutput(String);
+
+impl PendingOutput {
+    fn new(path: &str) -> Self {
+        let mut pending = PENDING_OUTPUTS.lock().unwrap_or_else(|e| e.into_inner());
+        pending.push(path.to_string());
+        Self(path.to_string())
+    }
+}
+
+impl Drop for PendingOutput {
+    fn drop(&mut self) {
+        let mut pending = PENDING_OUTPUTS.lock().unwrap_or_else(|e| e.into_inner());
+        pending.retain(|path| *path != self.0);
+    }
+}
+
+/// Whether the --job-timeout deadline (if any) has passed
+fn deadline_passed(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+/// Shorten the request timeout so it can't run past the --job-timeout deadline
+fn cap_timeout(config: RequestConfig, deadline: Option<Instant>) -> RequestConfig {
+    match deadline {
+        Some(deadline) => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let timeout = config.timeout.map_or(remaining, |t| t.min(remaining));
+            config.timeout(timeout)
+        }
+        None => config,
+    }
+}
+
+/// Download a single URL into the -o file over several ranged connections
+fn execute_segmented(
+    client: &MinimalCurl,
+    args: &Args,
+    headers: &[(String, String)],
+    segments: usize,
+) -> ExitCode {
+    let url = &args.urls[0];
+    let mut config = build_config(0, args, HttpMethod::Get, headers);
+    if let Some(ref output) = args.output {
+        config = config.output_file(output);
+    }
+
+    match client.download_segmented(&config, segments) {
+        Ok((bytes, used)) => {
+            if !args.silent && used < segments {
+                eprintln!(
+                    "Note: {} doesn't support byte ranges; downloaded over one connection",
+                    url
+                );
+            }
+            if args.verbose {
+                eprintln!("* Downloaded {} bytes in {} segment(s)", bytes, used);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            report_error(&config, &e, args);
+            ExitCode::from(22)
+        }
+    }
+}
+
+/// Execute requests sequentially with connection reuse
+fn execute_sequential(
+    client: &MinimalCurl,
+    start: usize,
+    args: &Args,
+    method: HttpMethod,
+    headers: &[(String, String)],
+    mut concat: Option<&mut ConcatWriter>,
+    deadline: Option<Instant>,
+) -> BatchOutcome {
+    let mut all_success = true;
+    let mut failed = Vec::new();
+    let mut body_hashes = Vec::new();
+    let mut latencies = Vec::new();
+    let mut timed_out = Vec::new();
+    let mut cancelled = Vec::new();
+    let urls = &args.urls;
+    // Streamed URLs are separated like a batch even when only one has arrived
+    let multi = urls.len() > 1 || args.stdin;
+
+    for (idx, url) in urls.iter().enumerate().skip(start) {
+        if idx > start {
+            wait_between(args, deadline);
+        }
+        if interrupted() {
+            cancelled.extend(urls[idx..].iter().cloned());
+            break;
+        }
+        if deadline_passed(deadline) {
+            timed_out.extend(urls[idx..].iter().cloned());
+            break;
+        }
+
+        let mut config = cap_timeout(build_config(idx, args, method, headers), deadline);
+
+        // Add output file if specified (only for single URL)
+        if !multi {
+            if let Some(ref output) = args.output {
+                config = config.output_file(output);
+            }
+        }
+
+        let pending = config.output_file.as_deref().map(PendingOutput::new);
+        let result = client.execute(&config);
+        drop(pending);
+        write_trace(args, &config, &result);
+        write_warc(args, &config, &result);
+        let failures = failed.len();
+
+        match result {
+            Ok(response) => {
+                // With --fail a truncated body counts as a failure
+                let truncated = warn_if_truncated(&config, &response, args.silent) && args.fail;
+
+                print_summary(&config, Ok(&response), args, multi);
+
+                // Print headers if requested
+                if (args.include_headers || args.head_only) && !summary_only(args) {
+                    if multi {
+                        println!("=== {} ===", config.display_name());
+                    }
+                    println!("HTTP/1.1 {} {}", response.status, response.status_text);
+                    for key in response.headers.keys() {
+                        for value in response.get_all(key) {
+                            println!("{}: {}", key, value);
+                        }
+                    }
+                    println!();
+                }
+
+                if let Some(ref path) = args.etag_save {
+                    if let Err(e) = save_etag(path, &response) {
+                        if !args.silent {
+                            eprintln!("Error: Failed to write '{}': {}", path, e);
+                        }
+                        all_success = false;
+                    }
+                }
+
+                // Collect the body into the concatenated output file
+                if let Some(ref mut concat) = concat {
+                    if let Err(e) = concat.append(&response_bytes(&response, args)) {
+                        if !args.silent {
+                            eprintln!("Error writing to concat file: {}", e);
+                        }
+                        all_success = false;
+                    }
+                }
+
+                // Print body (unless head-only or output to file)
+                if !args.head_only
+                    && !summary_only(args)
+                    && concat.is_none()
+                    && args.output_tree.is_none()
+                    && (!multi || args.output.is_none())
+                {
+                    if multi && !args.include_headers {
+                        println!("=== {} ===", config.display_name());
+                    }
+                    print_response_body(&response, args);
+                    if multi {
+                        println!(); // Add newline between responses
+                    }
+                }
+
+                // Print timing if requested
+                if args.timing {
+                    print_timing(idx, &config, &response);
+                }
+
+                if args.hash_bodies {
+                    body_hashes.push((idx, hash_body(&response)));
+                }
+                if args.percentiles {
+                    latencies.push(response.elapsed);
+                }
+
+                // 304 Not Modified is the expected "unchanged" answer to --etag-compare,
+                // and with --expect-status any response that got this far matched
+                let expected = args.expect_status.is_some()
+                    || (args.etag_compare.is_some() && response.status == 304);
+                if truncated || (!response.is_success() && !expected) {
+                    all_success = false;
+                    failed.push(url.clone());
+                }
+            }
+            // Cut short by the job deadline rather than a failure of its own
+            Err(_) if deadline_passed(deadline) => timed_out.push(url.clone()),
+            Err(e) => {
+                print_summary(&config, Err(&e), args, multi);
+                report_error(&config, &e, args);
+                all_success = false;
+                failed.push(url.clone());
+            }
+        }
+        record_done(failed.len() > failures);
+    }
+
+    BatchOutcome {
+        all_success,
+        failed,
+        timed_out,
+        cancelled,
+        body_hashes,
+        latencies,
+    }
+}
+
+/// Fetch the URLs given as arguments, then each URL read from stdin as soon as
+/// its line arrives, until EOF, Ctrl-C or the job deadline. The URLs are
+/// appended to `args.urls` so the summary and retry queue see all of them.
+fn execute_stdin(
+    client: &MinimalCurl,
+    args: &mut Args,
+    method: HttpMethod,
+    headers: &[(String, String)],
+    mut concat: Option<&mut ConcatWriter>,
+    deadline: Option<Instant>,
+) -> BatchOutcome {
+    let mut outcome = execute_sequential(
+        client,
+        0,
+        args,
+        method,
+        headers,
+        concat.as_deref_mut(),
+        deadline,
+    );
+
+    for line in io::stdin().lock().lines() {
+        if interrupted() || deadline_passed(deadline) {
+            break;
+        }
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                if !args.silent {
+                    eprintln!("Error: Failed to read stdin: {}", e);
+                }
+                outcome.all_success = false;
+                break;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (label, url) = match parse_batch_line(line) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                if !args.silent {
+                    eprintln!("Error: {}", e);
+                }
+                outcome.all_success = false;
+                continue;
+            }
+        };
+        args.urls.push(url.to_string());
+        args.labels.push(label);
+
+        let start = args.urls.len() - 1;
+        let next = execute_sequential(
+            client,
+            start,
+            args,
+            method,
+            headers,
+            concat.as_deref_mut(),
+            deadline,
+        );
+        outcome.all_success &= next.all_success;
+        outcome.failed.extend(next.failed);
+        outcome.timed_out.extend(next.timed_out);
+        outcome.cancelled.extend(next.cancelled);
+        outcome.body_hashes.extend(next.body_hashes);
+        outcome.latencies.extend(next.latencies);
+        // Show each result before blocking on the next line
+        let _ = io::stdout().flush();
+    }
+
+    outcome
+}
+
+/// Render a response as text for --diff: status line, optional headers, then body
+fn comparable_text(response: &CurlResponse, args: &Args) -> String {
+    let mut text = format!("HTTP/1.1 {} {}\n", response.status, response.status_text);
+
+    if args.diff_headers {
+        // Sort so header order differences don't show up as changes
+        let mut headers: Vec<_> = response
+            .headers
+            .iter()
+            .filter(|(key, _)| *key != "date" && !args.diff_ignore_headers.contains(key))
+            .collect();
+        headers.sort();
+        for (key, value) in headers {
+            text.push_str(&format!("{}: {}\n", key, value));
+        }
+        text.push('\n');
+    }
+
+    text.push_str(&response.body);
+    text
+}
+
+/// Fetch exactly two URLs and print a unified diff of the responses.
+/// Exit codes follow diff(1): 0 identical, 1 different, 2 trouble.
+fn execute_diff(
+    client: &MinimalCurl,
+    args: &Args,
+    method: HttpMethod,
+    headers: &[(String, String)],
+) -> ExitCode {
+    if args.urls.len() != 2 {
+        if !args.silent {
+            eprintln!("Error: --diff requires exactly two URLs");
+        }
+        return ExitCode::from(2);
+    }
 
-                // Print timing if requested
-                if args.timing {
-                    eprintln!(
-                        "[{}] {} - {} {} - {:.2}ms",
-                        idx + 1,
-                        url,
-                        response.status,
-                        response.status_text,
-                        elapsed.as_secs_f64() * 1000.0
-                    );
+    let mut texts = Vec::with_capacity(2);
+    for (idx, url) in args.urls.iter().enumerate() {
+        let config = build_config(idx, args, method, headers);
+        match client.execute(&config) {
+            Ok(response) => texts.push(comparable_text(&response, args)),
+            Err(e) => {
+                if !args.silent {
+                    eprintln!("Error fetching {}: {}", url, e);
                 }
+                return ExitCode::from(2);
+            }
+        }
+    }
 
-                if !response.is_success() {
-                    all_success = false;
-                }
+    match diff::unified_diff(&texts[0], &texts[1], &args.urls[0], &args.urls[1]) {
+        Some(output) => {
+            print!("{}", output);
+            ExitCode::from(1)
+        }
+        None => ExitCode::SUCCESS,
+    }
+}
+
+/// Re-send the requests of a HAR capture one by one for --replay, printing
+/// each status next to the recorded one. Exit codes follow --diff: 0 all
+/// matched, 1 something differed, 2 trouble.
+#[cfg(feature = "serde")]
+fn execute_replay(client: &MinimalCurl, args: &Args, path: &str) -> ExitCode {
+    let entries = match std::fs::read_to_string(path)
+        .map_err(CurlError::from)
+        .and_then(|json| bcurl::har::parse(&json))
+    {
+        Ok(entries) => entries,
+        Err(e) => {
+            if !args.silent {
+                eprintln!("Error: Failed to read '{}': {}", path, e);
             }
+            return ExitCode::from(2);
+        }
+    };
+
+    let (mut differed, mut skipped) = (0, 0);
+    for (idx, entry) in entries.iter().enumerate() {
+        let request = format!(
+            "[{}/{}] {} {}",
+            idx + 1,
+            entries.len(),
+            entry.method,
+            entry.url
+        );
+        let config = match entry.request() {
+            Ok(config) => config
+                .verbose(args.verbose)
+                .compression(args.compression)
+                .timeout(Duration::from_secs(args.timeout)),
             Err(e) => {
-                if !args.silent {
-                    eprintln!("Error fetching {}: {}", url, e);
+                println!("{} skipped: {}", request, e);
+                skipped += 1;
+                continue;
+            }
+        };
+        let response = match client.execute(&config) {
+            Ok(response) => response,
+            Err(e) => {
+                println!("{} error: {}", request, e);
+                differed += 1;
+                continue;
+            }
+        };
+
+        let body_diff = entry
+            .response_body
+            .as_ref()
+            .filter(|_| args.compare)
+            .and_then(|recorded| {
+                diff::unified_diff(
+                    &String::from_utf8_lossy(recorded),
+                    &String::from_utf8_lossy(response.bytes()),
+                    "recorded",
+                    "replayed",
+                )
+            });
+        let mut line = format!("{} {}", request, response.status);
+        if response.status != entry.status {
+            line.push_str(&format!(", recorded {}", entry.status));
+        }
+        if body_diff.is_some() {
+            line.push_str(", body differs");
+        }
+        println!("{}", line);
+        if response.status != entry.status || body_diff.is_some() {
+            differed += 1;
+        }
+        if let Some(body_diff) = body_diff {
+            print!("{}", body_diff);
+        }
+    }
+
+    if !args.silent {
+        eprintln!(
+            "* Replayed {} requests: {} differed, {} skipped",
+            entries.len() - skipped,
+            differed,
+            skipped
+        );
+    }
+    if differed > 0 {
+        ExitCode::from(1)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn execute_replay(_client: &MinimalCurl, args: &Args, _path: &str) -> ExitCode {
+    if !args.silent {
+        eprintln!("Error: --replay needs bcurl built with the `serde` feature");
+    }
+    ExitCode::from(2)
+}
+
+/// Live view of a parallel batch for --dashboard: one line per host with
+/// requests in flight, redrawn in place on stderr
+struct Dashboard {
+    start: Instant,
+    /// Host of each URL in the batch
+    hosts: Vec<String>,
+    /// Lines currently on screen
+    lines: usize,
+}
+
+impl Dashboard {
+    /// Widest URL shown; longer ones would wrap and break the redraw
+    const URL_WIDTH: usize = 60;
+
+    fn new(urls: &[String]) -> Self {
+        let hosts = urls.iter().map(|url| host_key(url)).collect();
+        Self {
+            start: Instant::now(),
+            hosts,
+            lines: 0,
+        }
+    }
+
+    /// Replace the previous frame with the requests in `started`
+    fn draw(&mut self, urls: &[String], started: &HashMap<usize, Instant>) {
+        self.clear();
+        let frame = self.frame(urls, started, Instant::now());
+        let mut stderr = io::stderr().lock();
+        for line in &frame {
+            let _ = writeln!(stderr, "{}", line);
+        }
+        self.lines = frame.len();
+    }
+
+    /// The lines for the requests in `started`, which maps each running URL's
+    /// index to when it started. Each host shows how many it has in flight and
+    /// the longest-running one, with its own elapsed time as of `now`.
+    fn frame(
+        &self,
+        urls: &[String],
+        started: &HashMap<usize, Instant>,
+        now: Instant,
+    ) -> Vec<String> {
+        let mut active: BTreeMap<&str, (usize, usize, Instant)> = BTreeMap::new();
+        for (&idx, &start) in started {
+            let entry = active.entry(&self.hosts[idx]).or_insert((0, idx, start));
+            entry.0 += 1;
+            if (start, idx) < (entry.2, entry.1) {
+                (entry.1, entry.2) = (idx, start);
+            }
+        }
+
+        active
+            .iter()
+            .map(|(host, &(count, idx, start))| {
+                let url: String = urls[idx].chars().take(Self::URL_WIDTH).collect();
+                format!(
+                    "{:<24} {:>4} in flight {:>7.1}s  {}",
+                    host,
+                    count,
+                    now.saturating_duration_since(start).as_secs_f64(),
+                    url
+                )
+            })
+            .collect()
+    }
+
+    /// Erase the frame so ordinary output can be printed
+    fn clear(&mut self) {
+        if self.lines > 0 {
+            eprint!("\x1b[{}A\x1b[J", self.lines);
+            self.lines = 0;
+        }
+    }
+
+    /// Erase the frame for good and print a one-line summary
+    fn finish(mut self, finished: &[bool]) {
+        self.clear();
+        let mut hosts: Vec<&str> = self.hosts.iter().map(String::as_str).collect();
+        hosts.sort_unstable();
+        hosts.dedup();
+        eprintln!(
+            "{}/{} requests to {} host(s) done in {:.1}s",
+            finished.iter().filter(|&&done| done).count(),
+            finished.len(),
+            hosts.len(),
+            self.start.elapsed().as_secs_f64()
+        );
+    }
+}
+
+/// `host:port` of `url`, or the whole string if it doesn't parse
+fn host_key(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(parsed) => parsed[url::Position::BeforeHost..url::Position::AfterPort].to_string(),
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Hands out parallel work round-robin across hosts, so a batch dominated by
+/// one host doesn't fill every worker with it while other hosts wait
+struct HostScheduler<T> {
+    /// One queue per host, in order of first appearance; empty ones are dropped
+    queues: VecDeque<VecDeque<T>>,
+}
+
+impl<T> HostScheduler<T> {
+    fn new(items: impl IntoIterator<Item = (String, T)>) -> Self {
+        let mut order: Vec<String> = Vec::new();
+        let mut queues: Vec<VecDeque<T>> = Vec::new();
+        for (host, item) in items {
+            match order.iter().position(|h| *h == host) {
+                Some(pos) => queues[pos].push_back(item),
+                None => {
+                    order.push(host);
+                    queues.push(VecDeque::from([item]));
                 }
-                all_success = false;
             }
         }
+        Self {
+            queues: queues.into(),
+        }
+    }
+
+    /// The next item from the next host in turn
+    fn next(&mut self) -> Option<T> {
+        let mut queue = self.queues.pop_front()?;
+        let item = queue.pop_front();
+        if !queue.is_empty() {
+            self.queues.push_back(queue);
+        }
+        item
+    }
+}
+
+/// Holds results that arrive out of order and releases them in input order
+/// as soon as every earlier result is in
+struct ReorderBuffer<T> {
+    next: usize,
+    waiting: BTreeMap<usize, T>,
+}
+
+impl<T> ReorderBuffer<T> {
+    fn new() -> Self {
+        Self {
+            next: 0,
+            waiting: BTreeMap::new(),
+        }
+    }
+
+    /// Add the result for `idx` and return every result that is now ready
+    fn push(&mut self, idx: usize, item: T) -> Vec<T> {
+        self.hold(idx, item);
+        let mut ready = Vec::new();
+        while let Some(item) = self.waiting.remove(&self.next) {
+            ready.push(item);
+            self.next += 1;
+        }
+        ready
+    }
+
+    /// Add the result for `idx` without releasing anything
+    fn hold(&mut self, idx: usize, item: T) {
+        self.waiting.insert(idx, item);
     }
 
-    all_success
+    /// Everything still waiting, in order, skipping gaps that will never fill
+    fn drain(self) -> impl Iterator<Item = T> {
+        self.waiting.into_values()
+    }
 }
 
-/// Execute requests in parallel using threads
+/// Execute requests in parallel using threads.
+///
+/// At most --parallel-max requests run at once, taken round-robin by host.
+/// Output is printed once every request is done, in input order. With
+/// --parallel-immediate each result is printed as soon as it arrives, in
+/// completion order, or in input order when --stable-order is also set.
 fn execute_parallel(
-    client: Arc<MinimalCurl>,
+    client: &MinimalCurl,
     urls: Vec<String>,
     args: &Args,
     method: HttpMethod,
     headers: Vec<(String, String)>,
-) -> bool {
+    mut concat: Option<&mut ConcatWriter>,
+    deadline: Option<Instant>,
+) -> BatchOutcome {
     let total_start = Instant::now();
     let silent = args.silent;
-    let verbose = args.verbose;
     let include_headers = args.include_headers;
     let head_only = args.head_only;
-    let follow_redirects = args.follow_redirects;
-    let compression = args.compression;
-    let timeout = args.timeout;
+    let summary_only = summary_only(args);
     let timing = args.timing;
-    let data = args.data.clone();
-
-    // Spawn threads for each URL
-    let handles: Vec<_> = urls
-        .into_iter()
-        .enumerate()
-        .map(|(idx, url)| {
-            let client = Arc::clone(&client);
-            let headers = headers.clone();
-            let data = data.clone();
 
-            thread::spawn(move || {
-                let start = Instant::now();
-
-                // Build request configuration
-                let mut config = RequestConfig::new(&url)
-                    .method(method)
-                    .follow_redirects(follow_redirects)
-                    .verbose(verbose)
-                    .include_headers(include_headers)
-                    .compression(compression)
-                    .timeout(Duration::from_secs(timeout));
-
-                // Add data if provided
-                if let Some(ref data) = data {
-                    config = config.data(data.clone());
-                }
-
-                // Add headers
-                for (key, value) in &headers {
-                    config = config.header(key.clone(), value.clone());
-                }
+    // Up to --parallel-max workers take URLs from the scheduler, interleaving
+    // hosts. None are started after Ctrl-C or the job deadline.
+    let scheduler = Arc::new(Mutex::new(HostScheduler::new(urls.iter().enumerate().map(
+        |(idx, url)| {
+            (
+                host_key(url),
+                (idx, build_config(idx, args, method, &headers)),
+            )
+        },
+    ))));
+    // When each request still running started, for --dashboard
+    let started = Arc::new(Mutex::new(HashMap::new()));
+    let (tx, rx) = mpsc::channel();
+    for _ in 0..args.parallel_max.min(urls.len()) {
+        let client = client.clone();
+        let scheduler = Arc::clone(&scheduler);
+        let started = Arc::clone(&started);
+        let tx = tx.clone();
 
+        thread::spawn(move || {
+            while !interrupted() && !deadline_passed(deadline) {
+                let Some((idx, config)) = scheduler.lock().unwrap().next() else {
+                    break;
+                };
+                let config = cap_timeout(config, deadline);
+                started.lock().unwrap().insert(idx, Instant::now());
                 let result = client.execute(&config);
-                let elapsed = start.elapsed();
-
-                (idx, url, result, elapsed)
-            })
-        })
-        .collect();
-
-    // Collect results
-    let mut results: Vec<_> = handles
-        .into_iter()
-        .map(|h| h.join().expect("Thread panicked"))
-        .collect();
-
-    // Sort by original index to maintain order
-    results.sort_by_key(|(idx, _, _, _)| *idx);
+                started.lock().unwrap().remove(&idx);
+                // Fails only if --job-timeout already gave up on this request
+                if tx.send((idx, config, result)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
 
     let mut all_success = true;
+    let mut failed = Vec::new();
+    let mut body_hashes = Vec::new();
+    let mut latencies = Vec::new();
+    let mut timed_out = Vec::new();
 
-    // Print results
-    for (idx, url, result, elapsed) in results {
+    // Print one result
+    type Outcome = (usize, RequestConfig, Result<CurlResponse, CurlError>);
+    let mut emit = |(idx, config, result): Outcome| {
+        write_trace(args, &config, &result);
+        write_warc(args, &config, &result);
+        let failures = failed.len();
         match result {
             Ok(response) => {
+                // With --fail a truncated body counts as a failure
+                let truncated = warn_if_truncated(&config, &response, silent) && args.fail;
+
+                print_summary(&config, Ok(&response), args, urls.len() > 1);
+
                 // Print headers if requested
-                if include_headers || head_only {
-                    println!("=== {} ===", url);
+                if (include_headers || head_only) && !summary_only {
+                    println!("=== {} ===", config.display_name());
                     println!("HTTP/1.1 {} {}", response.status, response.status_text);
-                    for (key, value) in &response.headers {
-                        println!("{}: {}", key, value);
+                    for key in response.headers.keys() {
+                        for value in response.get_all(key) {
+                            println!("{}: {}", key, value);
+                        }
                     }
                     println!();
                 }
 
+                // Collect the body into the concatenated output file
+                if let Some(ref mut concat) = concat {
+                    if let Err(e) = concat.append(&response_bytes(&response, args)) {
+                        if !silent {
+                            eprintln!("Error writing to concat file: {}", e);
+                        }
+                        all_success = false;
+                    }
+                }
+
                 // Print body
-                if !head_only {
+                if !head_only && !summary_only && concat.is_none() && args.output_tree.is_none() {
                     if !include_headers {
-                        println!("=== {} ===", url);
+                        println!("=== {} ===", config.display_name());
                     }
-                    print!("{}", response.body);
+                    print_response_body(&response, args);
                     println!();
                 }
 
                 // Print timing if requested
                 if timing {
-                    eprintln!(
-                        "[{}] {} - {} {} - {:.2}ms",
-                        idx + 1,
-                        url,
-                        response.status,
-                        response.status_text,
-                        elapsed.as_secs_f64() * 1000.0
-                    );
+                    print_timing(idx, &config, &response);
+                }
+
+                if args.hash_bodies {
+                    body_hashes.push((idx, hash_body(&response)));
+                }
+                if args.percentiles {
+                    latencies.push(response.elapsed);
                 }
 
-                if !response.is_success() {
+                // With --expect-status any response that got this far matched
+                if truncated || (!response.is_success() && args.expect_status.is_none()) {
                     all_success = false;
+                    failed.push(idx);
                 }
             }
+            Err(_) if deadline_passed(deadline) => timed_out.push(idx),
             Err(e) => {
-                if !silent {
-                    eprintln!("Error fetching {}: {}", url, e);
-                }
+                print_summary(&config, Err(&e), args, urls.len() > 1);
+                report_error(&config, &e, args);
                 all_success = false;
+                failed.push(idx);
+            }
+        }
+        record_done(failed.len() > failures);
+    };
+
+    // Only draw on a terminal, where the cursor can be moved back over it
+    let mut dashboard =
+        (args.dashboard && !silent && io::stderr().is_terminal()).then(|| Dashboard::new(&urls));
+
+    // Collect results, leaving stragglers behind on Ctrl-C or once the job
+    // deadline passes
+    let mut finished = vec![false; urls.len()];
+    let mut received = 0;
+    let mut reorder = ReorderBuffer::new();
+    while received < urls.len() && !interrupted() {
+        if let Some(ref mut dashboard) = dashboard {
+            let running = started.lock().unwrap().clone();
+            dashboard.draw(&urls, &running);
+        }
+        let wait = match deadline {
+            Some(deadline) => deadline
+                .saturating_duration_since(Instant::now())
+                .min(POLL_INTERVAL),
+            None => POLL_INTERVAL,
+        };
+        match rx.recv_timeout(wait) {
+            Ok(result) => {
+                received += 1;
+                finished[result.0] = true;
+                if let Some(ref mut dashboard) = dashboard {
+                    dashboard.clear();
+                }
+                if !args.parallel_immediate {
+                    // Printed all at once below
+                    reorder.hold(result.0, result);
+                } else if args.stable_order {
+                    reorder
+                        .push(result.0, result)
+                        .into_iter()
+                        .for_each(&mut emit);
+                } else {
+                    emit(result);
+                }
             }
+            Err(mpsc::RecvTimeoutError::Timeout) if !deadline_passed(deadline) => {}
+            Err(_) => break,
         }
     }
+    if let Some(dashboard) = dashboard {
+        dashboard.finish(&finished);
+    }
+    reorder.drain().for_each(&mut emit);
 
     if timing {
         let total_elapsed = total_start.elapsed();
@@ -447,11 +2887,29 @@ fn execute_parallel(
         );
     }
 
-    all_success
+    // Requests still running when we stopped waiting never reported back
+    let unfinished = (0..urls.len()).filter(|&idx| !finished[idx]);
+    let mut cancelled = Vec::new();
+    if interrupted() {
+        cancelled.extend(unfinished);
+    } else {
+        timed_out.extend(unfinished);
+    }
+
+    failed.sort_unstable();
+    timed_out.sort_unstable();
+    BatchOutcome {
+        all_success,
+        failed: failed.into_iter().map(|idx| urls[idx].clone()).collect(),
+        timed_out: timed_out.into_iter().map(|idx| urls[idx].clone()).collect(),
+        cancelled: cancelled.into_iter().map(|idx| urls[idx].clone()).collect(),
+        body_hashes,
+        latencies,
+    }
 }
 
 fn main() -> ExitCode {
-    let args = match parse_args() {
+    let mut args = match parse_args() {
         Ok(args) => args,
         Err(e) => {
             eprintln!("Error: {}", e);
@@ -464,7 +2922,7 @@ fn main() -> ExitCode {
     let method = if args.head_only {
         HttpMethod::Head
     } else {
-        match parse_method(&args.method) {
+        match HttpMethod::try_from(args.method.as_str()) {
             Ok(m) => m,
             Err(e) => {
                 if !args.silent {
@@ -491,23 +2949,451 @@ fn main() -> ExitCode {
         }
     }
 
+    // Label a --data-urlencode body as a form unless the user chose a type
+    let has_content_type = headers
+        .iter()
+        .any(|(key, _)| key.eq_ignore_ascii_case("content-type"));
+    if args.data_urlencoded && !has_content_type {
+        headers.push((
+            "Content-Type".to_string(),
+            "application/x-www-form-urlencoded".to_string(),
+        ));
+    }
+
+    // Label a binary upload by its extension unless the user chose a type
+    if args.auto_content_type {
+        let guessed = args
+            .data_binary_file
+            .as_deref()
+            .filter(|_| !has_content_type)
+            .and_then(guess_content_type);
+        if let Some(mime) = guessed {
+            headers.push(("Content-Type".to_string(), mime.to_string()));
+        }
+    }
+
+    // Fetch a bearer token from the credential helper, if configured
+    if let Some(ref command) = args.bearer_cmd {
+        match run_token_command(command) {
+            Ok(token) => headers.push(("Authorization".to_string(), format!("Bearer {}", token))),
+            Err(e) => {
+                if !args.silent {
+                    eprintln!("Error: {}", e);
+                }
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    // Make the request conditional on the ETag saved by an earlier run
+    if let Some(ref path) = args.etag_compare {
+        match read_etag(path) {
+            Ok(Some(etag)) => headers.push(("If-None-Match".to_string(), etag)),
+            Ok(None) => {}
+            Err(e) => {
+                if !args.silent {
+                    eprintln!("Error: Failed to read '{}': {}", path, e);
+                }
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
     // Create client with appropriate settings
     // The client maintains connection pool for reuse
-    let client = MinimalCurl::with_config(args.follow_redirects, Duration::from_secs(args.timeout));
+    let mut options = ClientOptions::new()
+        .follow_redirects(args.follow_redirects)
+        .timeout(Duration::from_secs(args.timeout));
+    // An explicit -x wins over the proxy environment variables
+    match args.proxy {
+        Some(ref proxy) => options = options.proxy(proxy.clone()),
+        None => options = options.proxy_from_env(),
+    }
+    for (host, proxy) in &args.proxy_map {
+        options = options.host_proxy(host.clone(), proxy.clone());
+    }
+    if let Some(ref user) = args.proxy_user {
+        options = options.proxy_user(user.clone());
+    }
+    options = options
+        .proxy_tunnel(args.proxy_tunnel)
+        .tcp_nodelay(args.tcp_nodelay)
+        .insecure(args.insecure);
+    if args.insecure && !args.silent {
+        eprintln!("Warning: --insecure: TLS certificates are not verified");
+    }
+    let mut client = match MinimalCurl::with_options(options) {
+        Ok(client) => client,
+        Err(e) => {
+            if !args.silent {
+                eprintln!("Error: {}", e);
+            }
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.cookie_file.is_some() || args.cookie_jar.is_some() {
+        let mut jar = CookieJar::new();
+        if let Some(ref path) = args.cookie_file {
+            match cookies::load_cookies(path) {
+                Ok(cookies) => jar.extend(cookies),
+                // A jar file that doesn't exist yet starts the session empty
+                Err(CurlError::IoError(e)) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    if !args.silent {
+                        eprintln!("Error: Failed to read '{}': {}", path, e);
+                    }
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        client = client.with_cookie_jar(jar);
+    }
+
+    if let Some(ref path) = args.replay {
+        let code = execute_replay(&client, &args, path);
+        print_pool_stats(&client, &args);
+        save_cookie_jar(&client, &args);
+        return code;
+    }
+
+    if args.diff {
+        let code = execute_diff(&client, &args, method, &headers);
+        print_pool_stats(&client, &args);
+        save_cookie_jar(&client, &args);
+        return code;
+    }
+
+    if let Some(segments) = args.parallel_segments {
+        let code = execute_segmented(&client, &args, &headers, segments);
+        save_cookie_jar(&client, &args);
+        return code;
+    }
 
     // Execute requests
-    let success = if args.parallel && args.urls.len() > 1 {
+    // Many URLs fetched one by one is slow; enable or suggest --parallel
+    let many_urls = args.urls.len() > args.parallel_threshold;
+    let parallel = !args.no_parallel && (args.parallel || (args.auto_parallel && many_urls));
+    if many_urls && !parallel && !args.no_parallel && !args.silent {
+        eprintln!(
+            "Hint: fetching {} URLs one at a time; add --parallel (or --auto-parallel) to fetch them concurrently",
+            args.urls.len()
+        );
+    }
+
+    let mut concat = match args.output_concat {
+        Some(ref path) => match ConcatWriter::create(path, args.concat_separator.clone()) {
+            Ok(writer) => Some(writer),
+            Err(e) => {
+                if !args.silent {
+                    eprintln!("Error: Failed to create '{}': {}", path, e);
+                }
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    // Requests are appended as they finish, so start from an empty file
+    if let Some(path) = args.trace_ascii.as_ref().filter(|path| *path != "-") {
+        if let Err(e) = File::create(path) {
+            if !args.silent {
+                eprintln!("Error: Failed to create '{}': {}", path, e);
+            }
+            return ExitCode::FAILURE;
+        }
+    }
+
+    // Records are appended too, after the warcinfo record that opens the file
+    if let Some(ref path) = args.warc_output {
+        if let Err(e) = std::fs::write(path, warc::warcinfo(SystemTime::now())) {
+            if !args.silent {
+                eprintln!("Error: Failed to create '{}': {}", path, e);
+            }
+            return ExitCode::FAILURE;
+        }
+    }
+
+    if let Some(header) = args.output_format.header() {
+        println!("{}", header);
+    }
+
+    install_interrupt_handler(args.silent);
+    install_progress_handler((!args.stdin).then_some(args.urls.len()));
+    let deadline = args.job_timeout.map(|timeout| Instant::now() + timeout);
+    let outcome = if args.stdin {
+        // Streamed from another process; each URL runs once its line arrives
+        execute_stdin(
+            &client,
+            &mut args,
+            method,
+            &headers,
+            concat.as_mut(),
+            deadline,
+        )
+    } else if parallel && args.urls.len() > 1 {
         // Parallel execution for multiple URLs
-        let client = Arc::new(client);
-        execute_parallel(client, args.urls.clone(), &args, method, headers)
+        execute_parallel(
+            &client,
+            args.urls.clone(),
+            &args,
+            method,
+            headers,
+            concat.as_mut(),
+            deadline,
+        )
     } else {
         // Sequential execution with connection reuse
-        execute_sequential(&client, &args.urls, &args, method, &headers)
+        execute_sequential(
+            &client,
+            0,
+            &args,
+            method,
+            &headers,
+            concat.as_mut(),
+            deadline,
+        )
     };
 
-    if success {
+    print_pool_stats(&client, &args);
+    save_cookie_jar(&client, &args);
+    print_duplicate_bodies(&outcome, &args);
+    print_percentiles(&outcome, &args);
+
+    if let Some(ref path) = args.retry_queue {
+        if let Err(e) = save_retry_queue(path, &args.urls, &outcome) {
+            if !args.silent {
+                eprintln!("Error: Failed to write retry queue '{}': {}", path, e);
+            }
+        }
+    }
+
+    if interrupted() {
+        let total = args.urls.len();
+        let unfinished = outcome.cancelled.len() + outcome.timed_out.len();
+        // The combined file is missing the cancelled bodies
+        if !outcome.cancelled.is_empty() {
+            if let Some(ref path) = args.output_concat {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+        if !args.silent {
+            eprintln!(
+                "Interrupted: {} of {} requests completed",
+                total - unfinished,
+                total
+            );
+            for url in &outcome.cancelled {
+                eprintln!("  cancelled: {}", url);
+            }
+            for url in &outcome.timed_out {
+                eprintln!("  timed out: {}", url);
+            }
+        }
+        ExitCode::from(130) // 128 + SIGINT, as shells report it
+    } else if !outcome.timed_out.is_empty() {
+        if !args.silent {
+            eprintln!(
+                "Job timeout: {} of {} requests completed",
+                args.urls.len() - outcome.timed_out.len(),
+                args.urls.len()
+            );
+            for url in &outcome.timed_out {
+                eprintln!("  timed out: {}", url);
+            }
+        }
+        ExitCode::from(28) // curl's exit code for timeouts
+    } else if outcome.all_success {
         ExitCode::SUCCESS
     } else {
         ExitCode::from(22) // curl uses 22 for HTTP errors
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dashboard_frame() {
+        let urls: Vec<String> = [
+            "http://a.test/1",
+            "http://a.test/2",
+            "http://a.test/3",
+            "http://b.test/1",
+        ]
+        .iter()
+        .map(|url| url.to_string())
+        .collect();
+        let dashboard = Dashboard::new(&urls);
+        let now = Instant::now() + Duration::from_secs(10);
+
+        // a.test/3 is still queued, so it isn't shown or counted
+        let started = HashMap::from([
+            (1, now - Duration::from_millis(1500)),
+            (0, now - Duration::from_millis(2500)),
+            (3, now - Duration::from_millis(500)),
+        ]);
+        let frame = dashboard.frame(&urls, &started, now);
+        assert_eq!(frame.len(), 2);
+        assert_eq!(
+            frame[0],
+            format!("{:<24}    2 in flight     2.5s  http://a.test/1", "a.test")
+        );
+        assert_eq!(
+            frame[1],
+            format!("{:<24}    1 in flight     0.5s  http://b.test/1", "b.test")
+        );
+
+        assert!(dashboard.frame(&urls, &HashMap::new(), now).is_empty());
+    }
+
+    #[test]
+    fn test_host_scheduler_round_robin() {
+        let mut scheduler = HostScheduler::new(
+            [("a", "a1"), ("a", "a2"), ("a", "a3"), ("b", "b1")]
+                .map(|(host, item)| (host.to_string(), item)),
+        );
+        let order: Vec<&str> = std::iter::from_fn(|| scheduler.next()).collect();
+        assert_eq!(order, ["a1", "b1", "a2", "a3"]);
+        assert_eq!(scheduler.next(), None);
+    }
+
+    #[test]
+    fn test_reorder_buffer() {
+        let mut buffer = ReorderBuffer::new();
+        assert!(buffer.push(1, "b").is_empty());
+        assert!(buffer.push(2, "c").is_empty());
+        assert_eq!(buffer.push(0, "a"), ["a", "b", "c"]);
+        assert_eq!(buffer.push(3, "d"), ["d"]);
+
+        // Held results and ones behind a gap come out of drain, in order
+        let mut buffer = ReorderBuffer::new();
+        buffer.hold(2, "c");
+        buffer.hold(0, "a");
+        assert_eq!(buffer.push(4, "e"), ["a"]);
+        buffer.hold(7, "h");
+        assert_eq!(buffer.drain().collect::<Vec<_>>(), ["c", "e", "h"]);
+    }
+
+    #[test]
+    fn test_tree_path() {
+        let dir = Path::new("out");
+        assert_eq!(
+            tree_path(dir, "http://example.com/a/b.html"),
+            Some(dir.join("example.com").join("a").join("b.html"))
+        );
+        assert_eq!(
+            tree_path(dir, "http://example.com:8080/docs/"),
+            Some(dir.join("example.com_8080").join("docs").join("index.html"))
+        );
+        assert_eq!(
+            tree_path(dir, "http://example.com"),
+            Some(dir.join("example.com").join("index.html"))
+        );
+
+        // Different queries get different names, keeping the extension
+        let first = tree_path(dir, "http://example.com/list.json?page=1").unwrap();
+        let second = tree_path(dir, "http://example.com/list.json?page=2").unwrap();
+        assert_ne!(first, second);
+        for path in [&first, &second] {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            assert!(
+                name.starts_with("list_") && name.ends_with(".json"),
+                "{}",
+                name
+            );
+            assert_eq!(path.parent(), Some(dir.join("example.com").as_path()));
+        }
+
+        assert_eq!(tree_path(dir, "not a url"), None);
+    }
+
+    #[test]
+    fn test_percentile() {
+        let sorted: Vec<Duration> = (1..=10).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 50), Duration::from_millis(5));
+        assert_eq!(percentile(&sorted, 90), Duration::from_millis(9));
+        assert_eq!(percentile(&sorted, 99), Duration::from_millis(10));
+        assert_eq!(percentile(&sorted, 0), Duration::from_millis(1));
+        assert_eq!(
+            percentile(&[Duration::from_millis(7)], 50),
+            Duration::from_millis(7)
+        );
+    }
+
+    #[test]
+    fn test_parse_port_range() {
+        assert_eq!(parse_port_range("4000-4010"), Ok((4000, 4010)));
+        assert_eq!(parse_port_range("4000"), Ok((4000, 4000)));
+        for bad in ["4010-4000", "0-10", "70000", "a-b", "-", ""] {
+            assert_eq!(
+                parse_port_range(bad),
+                Err(format!("Invalid local port range: {}", bad))
+            );
+        }
+    }
+
+    #[test]
+    fn test_data_arg() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "name=bcurl&v=1").unwrap();
+        let path = file.path().to_str().unwrap();
+        assert_eq!(data_arg(&format!("@{}", path)).unwrap(), "name=bcurl&v=1");
+
+        let missing = format!("{}.missing", path);
+        let error = data_arg(&format!("@{}", missing)).unwrap_err();
+        assert!(
+            error.starts_with(&format!("Failed to read '{}': ", missing)),
+            "{}",
+            error
+        );
+
+        assert_eq!(data_arg("name=bcurl").unwrap(), "name=bcurl");
+        assert_eq!(data_arg("").unwrap(), "");
+    }
+
+    #[test]
+    fn test_retry_queue_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.txt");
+        let path = path.to_str().unwrap();
+
+        // No file yet means nothing is queued
+        assert!(read_retry_queue(path).unwrap().is_empty());
+
+        let urls: Vec<String> = [
+            "http://a.test/",
+            "http://b.test/",
+            "http://c.test/",
+            "http://d.test/",
+        ]
+        .iter()
+        .map(|url| url.to_string())
+        .collect();
+        let outcome = BatchOutcome {
+            failed: vec![urls[2].clone()],
+            timed_out: vec![urls[0].clone()],
+            cancelled: vec![urls[3].clone()],
+            ..Default::default()
+        };
+        save_retry_queue(path, &urls, &outcome).unwrap();
+
+        // Only the URLs that didn't succeed stay queued, in run order
+        assert_eq!(
+            read_retry_queue(path).unwrap(),
+            [urls[0].as_str(), &urls[2], &urls[3]]
+        );
+
+        // Blank lines and stray whitespace are skipped
+        std::fs::write(path, "  http://a.test/ \n\nhttp://b.test/\n").unwrap();
+        assert_eq!(
+            read_retry_queue(path).unwrap(),
+            ["http://a.test/", "http://b.test/"]
+        );
+
+        // Once everything succeeds the queue file goes away
+        save_retry_queue(path, &urls, &BatchOutcome::default()).unwrap();
+        assert!(!Path::new(path).exists());
+        save_retry_queue(path, &urls, &BatchOutcome::default()).unwrap();
+    }
+}
@@ -0,0 +1,314 @@
+//! `multipart/form-data` request bodies, as sent by curl's `-F`
+
+use crate::{CurlError, FileUpload};
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// One field of a `multipart/form-data` body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultipartPart {
+    /// A plain text field
+    Text { name: String, value: String },
+    /// A file, read from disk each time the request is sent
+    File {
+        name: String,
+        filename: String,
+        path: String,
+        content_type: Option<String>,
+    },
+    /// File content already in memory, such as data piped in on stdin
+    Data {
+        name: String,
+        filename: Option<String>,
+        content_type: Option<String>,
+        data: Vec<u8>,
+    },
+}
+
+impl MultipartPart {
+    /// A text field
+    pub fn text(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self::Text {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    /// A file field, sent with the file's base name as its filename
+    pub fn file(name: impl Into<String>, path: impl Into<String>) -> Self {
+        let path = path.into();
+        Self::File {
+            name: name.into(),
+            filename: base_name(&path),
+            path,
+            content_type: None,
+        }
+    }
+
+    /// Parse a curl `-F` argument.
+    ///
+    /// `name=value` is a text field and `name=@path` uploads a file; `@-` reads
+    /// the content from stdin straight away, so the part can be sent again on a
+    /// retry. File fields accept `;type=<mime>` and `;filename=<name>` suffixes:
+    ///
+    /// ```
+    /// use bcurl::multipart::MultipartPart;
+    ///
+    /// let part = MultipartPart::parse("upload=@report.csv;type=text/csv;filename=data.csv").unwrap();
+    /// assert_eq!(
+    ///     part,
+    ///     MultipartPart::File {
+    ///         name: "upload".to_string(),
+    ///         filename: "data.csv".to_string(),
+    ///         path: "report.csv".to_string(),
+    ///         content_type: Some("text/csv".to_string()),
+    ///     }
+    /// );
+    /// ```
+    pub fn parse(spec: &str) -> Result<Self, CurlError> {
+        let (name, value) = spec
+            .split_once('=')
+            .filter(|(name, _)| !name.is_empty())
+            .ok_or_else(|| {
+                CurlError::InvalidConfig(format!("form field '{}' must be name=value", spec))
+            })?;
+
+        let Some(file) = value.strip_prefix('@') else {
+            return Ok(Self::text(name, value));
+        };
+
+        let mut options = file.split(';');
+        let path = options.next().unwrap_or_default();
+        let mut filename = None;
+        let mut content_type = None;
+        for option in options {
+            match option.split_once('=') {
+                Some(("type", mime)) => content_type = Some(mime.to_string()),
+                Some(("filename", value)) => filename = Some(value.trim_matches('"').to_string()),
+                _ => {
+                    return Err(CurlError::InvalidConfig(format!(
+                        "unknown option '{}' in form field '{}'",
+                        option, spec
+                    )))
+                }
+            }
+        }
+
+        if path == "-" {
+            let mut data = Vec::new();
+            std::io::stdin().read_to_end(&mut data)?;
+            return Ok(Self::Data {
+                name: name.to_string(),
+                filename,
+                content_type,
+                data,
+            });
+        }
+
+        Ok(Self::File {
+            name: name.to_string(),
+            filename: filename.unwrap_or_else(|| base_name(path)),
+            path: path.to_string(),
+            content_type,
+        })
+    }
+}
+
+/// The last component of `path`, used as the default upload filename
+fn base_name(path: &str) -> String {
+    Path::new(path).file_name().map_or_else(
+        || path.to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+/// A boundary that won't plausibly appear in any part
+pub(crate) fn boundary() -> String {
+    format!("------------------------bcurl{:016x}", crate::random_u64())
+}
+
+/// A multipart body ready to stream, see [`stream`]
+pub(crate) struct Body {
+    pub(crate) reader: Box<dyn Read + Send>,
+    /// Total length, for `Content-Length`
+    pub(crate) len: u64,
+    /// Where a file read error is kept, as for [`crate::FileUpload`]
+    pub(crate) error: Arc<Mutex<Option<std::io::Error>>>,
+}
+
+/// Stream `parts` as a body delimited by `boundary`. Files are opened now, to
+/// learn their sizes, but only read as the body is sent.
+pub(crate) fn stream(parts: &[MultipartPart], boundary: &str) -> Result<Body, CurlError> {
+    let error = Arc::default();
+    let mut reader: Box<dyn Read + Send> = Box::new(std::io::empty());
+    let mut len = 0;
+    // Headers and in-memory content between files
+    let mut pending = Vec::new();
+    for part in parts {
+        pending.extend_from_slice(&part_head(part, boundary));
+        match part {
+            MultipartPart::Text { value, .. } => pending.extend_from_slice(value.as_bytes()),
+            MultipartPart::File { path, .. } => {
+                let file = File::open(path)?;
+                let size = file.metadata()?.len();
+                len += pending.len() as u64 + size;
+                let head = Cursor::new(std::mem::take(&mut pending));
+                let upload = FileUpload::new(file, size, Arc::clone(&error));
+                reader = Box::new(reader.chain(head).chain(upload));
+            }
+            MultipartPart::Data { data, .. } => pending.extend_from_slice(data),
+        }
+        pending.extend_from_slice(b"\r\n");
+    }
+    pending.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    len += pending.len() as u64;
+    let reader = Box::new(reader.chain(Cursor::new(pending)));
+    Ok(Body { reader, len, error })
+}
+
+/// The boundary line and headers that start `part`
+fn part_head(part: &MultipartPart, boundary: &str) -> Vec<u8> {
+    let (name, filename, content_type) = match part {
+        MultipartPart::Text { name, .. } => (name, None, None),
+        MultipartPart::File {
+            name,
+            filename,
+            content_type,
+            ..
+        } => (name, Some(filename), content_type.as_ref()),
+        MultipartPart::Data {
+            name,
+            filename,
+            content_type,
+            ..
+        } => (name, filename.as_ref(), content_type.as_ref()),
+    };
+
+    let mut head = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+        boundary,
+        escape(name)
+    );
+    if let Some(filename) = filename {
+        head.push_str(&format!("; filename=\"{}\"", escape(filename)));
+    }
+    head.push_str("\r\n");
+    if let Some(content_type) = content_type {
+        head.push_str(&format!("Content-Type: {}\r\n", content_type));
+    } else if !matches!(part, MultipartPart::Text { .. }) {
+        head.push_str("Content-Type: application/octet-stream\r\n");
+    }
+    head.push_str("\r\n");
+    head.into_bytes()
+}
+
+/// Escape a name for a quoted `Content-Disposition` parameter, as browsers do
+fn escape(value: &str) -> String {
+    value
+        .replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_text_and_file() {
+        assert_eq!(
+            MultipartPart::parse("q=a;b=c").unwrap(),
+            MultipartPart::text("q", "a;b=c")
+        );
+        assert_eq!(
+            MultipartPart::parse("doc=@/tmp/report.pdf").unwrap(),
+            MultipartPart::file("doc", "/tmp/report.pdf")
+        );
+        assert!(matches!(
+            MultipartPart::parse("doc=@a.txt;charset=utf-8"),
+            Err(CurlError::InvalidConfig(_))
+        ));
+        assert!(MultipartPart::parse("=value").is_err());
+        assert!(MultipartPart::parse("novalue").is_err());
+    }
+
+    #[test]
+    fn test_parse_filename_override() {
+        match MultipartPart::parse("f=@x.bin;filename=\"y.bin\"").unwrap() {
+            MultipartPart::File { filename, path, .. } => {
+                assert_eq!(filename, "y.bin");
+                assert_eq!(path, "x.bin");
+            }
+            other => panic!("expected a file part, got {:?}", other),
+        }
+    }
+
+    /// The streamed body, checking it's as long as announced
+    fn read_all(parts: &[MultipartPart], boundary: &str) -> String {
+        let mut body = stream(parts, boundary).unwrap();
+        let mut bytes = Vec::new();
+        body.reader.read_to_end(&mut bytes).unwrap();
+        assert_eq!(bytes.len() as u64, body.len);
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_stream_structure() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "line 1\nline 2\n").unwrap();
+        let path = path.to_str().unwrap();
+
+        let parts = [
+            MultipartPart::text("title", "Q3 notes"),
+            MultipartPart::file("upload", path),
+            MultipartPart::File {
+                name: "copy".to_string(),
+                filename: "other.md".to_string(),
+                path: path.to_string(),
+                content_type: Some("text/markdown".to_string()),
+            },
+        ];
+        assert_eq!(
+            read_all(&parts, "B"),
+            "--B\r\n\
+             Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+             Q3 notes\r\n\
+             --B\r\n\
+             Content-Disposition: form-data; name=\"upload\"; filename=\"notes.txt\"\r\n\
+             Content-Type: application/octet-stream\r\n\r\n\
+             line 1\nline 2\n\r\n\
+             --B\r\n\
+             Content-Disposition: form-data; name=\"copy\"; filename=\"other.md\"\r\n\
+             Content-Type: text/markdown\r\n\r\n\
+             line 1\nline 2\n\r\n\
+             --B--\r\n"
+        );
+
+        let missing = [MultipartPart::file(
+            "f",
+            dir.path().join("gone").to_str().unwrap(),
+        )];
+        assert!(matches!(stream(&missing, "B"), Err(CurlError::IoError(_))));
+    }
+
+    #[test]
+    fn test_encode_in_memory_part() {
+        let parts = [MultipartPart::Data {
+            name: "csv".to_string(),
+            filename: Some("a\"b.csv".to_string()),
+            content_type: Some("text/csv".to_string()),
+            data: b"x,y\n".to_vec(),
+        }];
+        assert_eq!(
+            read_all(&parts, "XYZ"),
+            "--XYZ\r\n\
+             Content-Disposition: form-data; name=\"csv\"; filename=\"a%22b.csv\"\r\n\
+             Content-Type: text/csv\r\n\r\n\
+             x,y\n\r\n\
+             --XYZ--\r\n"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! WARC 1.1 records, as written by `--warc-output`
+//!
+//! Each exchange becomes a `request` record and a `response` record holding
+//! the HTTP messages. They are rebuilt from the [`RequestConfig`] and
+//! [`CurlResponse`] rather than captured off the wire, so the request shows
+//! the headers the config sets (plus `Host`) but not ones ureq adds, and the
+//! response body is the one bcurl read: without `Transfer-Encoding` and, when
+//! it was decompressed, without `Content-Encoding`, with `Content-Length`
+//! rewritten to match. Only the final hop of a redirect chain is recorded.
+
+use crate::{CurlResponse, HttpMethod, RequestConfig};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A `warcinfo` record naming the software, to start a file with
+pub fn warcinfo(date: SystemTime) -> Vec<u8> {
+    let fields = format!(
+        "software: bcurl/{}\r\nformat: WARC File Format 1.1\r\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    record(
+        "warcinfo",
+        &record_id(),
+        date,
+        None,
+        None,
+        "application/warc-fields",
+        fields.as_bytes(),
+    )
+}
+
+/// The `request` and `response` records of one exchange, which started at `date`
+pub fn exchange(config: &RequestConfig, response: &CurlResponse, date: SystemTime) -> Vec<u8> {
+    let target = &response.final_url;
+    let request_id = record_id();
+    let mut out = record(
+        "request",
+        &request_id,
+        date,
+        Some(target),
+        None,
+        "application/http;msgtype=request",
+        &http_request(config, target),
+    );
+    out.extend(record(
+        "response",
+        &record_id(),
+        date,
+        Some(target),
+        Some(&request_id),
+        "application/http;msgtype=response",
+        &http_response(config, response),
+    ));
+    out
+}
+
+fn record(
+    kind: &str,
+    id: &str,
+    date: SystemTime,
+    target: Option<&str>,
+    concurrent_to: Option<&str>,
+    content_type: &str,
+    block: &[u8],
+) -> Vec<u8> {
+    let mut head = format!(
+        "WARC/1.1\r\nWARC-Type: {}\r\nWARC-Record-ID: {}\r\nWARC-Date: {}\r\n",
+        kind,
+        id,
+        iso_date(date)
+    );
+    if let Some(target) = target {
+        head.push_str(&format!("WARC-Target-URI: {}\r\n", target));
+    }
+    if let Some(concurrent_to) = concurrent_to {
+        head.push_str(&format!("WARC-Concurrent-To: {}\r\n", concurrent_to));
+    }
+    head.push_str(&format!(
+        "Content-Type: {}\r\nContent-Length: {}\r\n\r\n",
+        content_type,
+        block.len()
+    ));
+    let mut out = head.into_bytes();
+    out.extend_from_slice(block);
+    out.extend_from_slice(b"\r\n\r\n");
+    out
+}
+
+fn record_id() -> String {
+    format!("<urn:uuid:{}>", crate::generate_trace_id())
+}
+
+/// The request as sent to `target`. A body streamed from a file or built from
+/// multipart parts isn't kept, so it's left out.
+fn http_request(config: &RequestConfig, target: &str) -> Vec<u8> {
+    let url = url::Url::parse(target).ok();
+    let path = url.as_ref().map_or("/", |url| url.path());
+    let query = url
+        .as_ref()
+        .and_then(|url| url.query())
+        .map(|query| format!("?{}", query))
+        .unwrap_or_default();
+    let mut head = format!("{} {}{} HTTP/1.1\r\n", config.method, path, query);
+    if let Some(host) = url.as_ref().and_then(|url| url.host_str()) {
+        match url.as_ref().and_then(|url| url.port()) {
+            Some(port) => head.push_str(&format!("Host: {}:{}\r\n", host, port)),
+            None => head.push_str(&format!("Host: {}\r\n", host)),
+        }
+    }
+    for (key, value) in config.headers.iter().chain(&config.trace_id) {
+        head.push_str(&format!("{}: {}\r\n", key, value));
+    }
+    head.push_str("\r\n");
+
+    let mut out = head.into_bytes();
+    if let Some(body) = config
+        .body_bytes
+        .as_deref()
+        .or(config.data.as_deref().map(str::as_bytes))
+    {
+        out.extend_from_slice(body);
+    }
+    out
+}
+
+/// The response as read, with framing headers that no longer apply replaced
+fn http_response(config: &RequestConfig, response: &CurlResponse) -> Vec<u8> {
+    let head_only = config.method == HttpMethod::Head;
+    let mut names: Vec<&String> = response.headers.keys().collect();
+    names.sort();
+
+    let mut head = format!("HTTP/1.1 {} {}\r\n", response.status, response.status_text);
+    for name in names {
+        let rewritten = match name.as_str() {
+            "transfer-encoding" => true,
+            "content-length" => !head_only,
+            "content-encoding" => config.decode_body,
+            _ => false,
+        };
+        if rewritten {
+            continue;
+        }
+        for value in response.get_all(name) {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+    }
+    if !head_only {
+        head.push_str(&format!(
+            "content-length: {}\r\n",
+            response.body_bytes.len()
+        ));
+    }
+    head.push_str("\r\n");
+
+    let mut out = head.into_bytes();
+    out.extend_from_slice(&response.body_bytes);
+    out
+}
+
+/// `date` as a UTC ISO 8601 timestamp to the second, e.g. `2024-05-01T12:00:00Z`
+fn iso_date(date: SystemTime) -> String {
+    let seconds = date.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's civil_from_days)
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[test]
+    fn test_iso_date() {
+        assert_eq!(iso_date(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            iso_date(UNIX_EPOCH + Duration::from_secs(951_825_599)),
+            "2000-02-29T11:59:59Z"
+        );
+        assert_eq!(
+            iso_date(UNIX_EPOCH + Duration::from_secs(1_793_318_400)),
+            "2026-10-30T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn test_exchange_records() {
+        let config = RequestConfig::new("http://example.com:8080/a?b=1")
+            .method(HttpMethod::Post)
+            .header("Content-Type", "text/plain")
+            .data("hi");
+        let mut headers = HashMap::new();
+        headers.insert("content-type".to_string(), "text/html".to_string());
+        headers.insert("content-encoding".to_string(), "gzip".to_string());
+        headers.insert("transfer-encoding".to_string(), "chunked".to_string());
+        let response = CurlResponse {
+            status: 200,
+            status_text: "OK".to_string(),
+            headers,
+            body: "<p>".to_string(),
+            body_bytes: b"<p>".to_vec(),
+            original_url: config.url.clone(),
+            final_url: config.url.clone(),
+            ..Default::default()
+        };
+
+        let out = exchange(&config, &response, UNIX_EPOCH);
+        let text = String::from_utf8(out).unwrap();
+        let records: Vec<&str> = text.split("WARC/1.1\r\n").skip(1).collect();
+        assert_eq!(records.len(), 2);
+
+        let (head, block) = records[0].split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("WARC-Type: request\r\nWARC-Record-ID: <urn:uuid:"));
+        assert!(head.contains("WARC-Date: 1970-01-01T00:00:00Z\r\n"));
+        assert!(head.contains("WARC-Target-URI: http://example.com:8080/a?b=1\r\n"));
+        assert_eq!(
+            block,
+            "POST /a?b=1 HTTP/1.1\r\nHost: example.com:8080\r\n\
+             Content-Type: text/plain\r\n\r\nhi\r\n\r\n"
+        );
+        let http_len = block.len() - 4;
+        assert!(head.ends_with(&format!("Content-Length: {}", http_len)));
+
+        let request_id = head
+            .lines()
+            .find_map(|line| line.strip_prefix("WARC-Record-ID: "))
+            .unwrap();
+        let (head, block) = records[1].split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("WARC-Type: response\r\n"));
+        assert!(head.contains(&format!("WARC-Concurrent-To: {}\r\n", request_id)));
+        assert!(head.contains("Content-Type: application/http;msgtype=response\r\n"));
+        assert_eq!(
+            block,
+            "HTTP/1.1 200 OK\r\ncontent-type: text/html\r\n\
+             content-length: 3\r\n\r\n<p>\r\n\r\n"
+        );
+    }
+}
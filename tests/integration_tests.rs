@@ -1,7 +1,17 @@
 //! Integration tests for bcurl
 
-use bcurl::{HttpMethod, MinimalCurl, RequestConfig};
+use bcurl::{
+    cookies, Algorithm, ClientOptions, CookieJar, CurlError, CurlResponse, EnvProxy, HttpMethod,
+    MinimalCurl, MultipartPart, Redirect, RedirectAction, RequestConfig, RetryPolicy, SharedPool,
+    Transport,
+};
 use mockito::{Matcher, Server};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tempfile::NamedTempFile;
 
 #[test]
@@ -19,7 +29,7 @@ fn test_get_request() {
 
     mock.assert();
     assert_eq!(response.status, 200);
-    assert_eq!(response.body, "Hello, World!");
+    assert_eq!(response.text(), "Hello, World!");
     assert!(response.is_success());
 }
 
@@ -39,7 +49,7 @@ fn test_get_request_with_path() {
 
     mock.assert();
     assert_eq!(response.status, 200);
-    assert_eq!(response.body, r#"{"users": []}"#);
+    assert_eq!(response.text(), r#"{"users": []}"#);
 }
 
 #[test]
@@ -59,7 +69,7 @@ fn test_post_request_with_data() {
 
     mock.assert();
     assert_eq!(response.status, 201);
-    assert!(response.body.contains("\"id\": 1"));
+    assert!(response.text().contains("\"id\": 1"));
 }
 
 #[test]
@@ -117,7 +127,195 @@ fn test_custom_headers() {
 
     mock.assert();
     assert_eq!(response.status, 200);
-    assert_eq!(response.body, "Authenticated!");
+    assert_eq!(response.text(), "Authenticated!");
+}
+
+#[test]
+fn test_basic_auth() {
+    let mut server = Server::new();
+    // base64("alice:s3cret") and base64("bob:")
+    let with_password = server
+        .mock("GET", "/private")
+        .match_header("Authorization", "Basic YWxpY2U6czNjcmV0")
+        .create();
+    let empty_password = server
+        .mock("GET", "/private")
+        .match_header("Authorization", "Basic Ym9iOg==")
+        .create();
+
+    let client = MinimalCurl::new();
+    let url = format!("{}/private", server.url());
+    let config = RequestConfig::new(&url).basic_auth("alice", Some("s3cret"));
+    assert_eq!(client.execute(&config).unwrap().status, 200);
+    let config = RequestConfig::new(&url).basic_auth("bob", None::<String>);
+    assert_eq!(client.execute(&config).unwrap().status, 200);
+
+    with_password.assert();
+    empty_password.assert();
+}
+
+#[test]
+fn test_token_refresh_on_unauthorized() {
+    let mut server = Server::new();
+    let expired = server
+        .mock("GET", "/me")
+        .match_header("Authorization", "Bearer old")
+        .with_status(401)
+        .expect(2)
+        .create();
+    let refreshed = server
+        .mock("GET", "/me")
+        .match_header("Authorization", "Bearer new")
+        .with_body("me")
+        .create();
+
+    let client = MinimalCurl::new();
+    let url = format!("{}/me", server.url());
+    let calls = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&calls);
+    let config = RequestConfig::new(&url)
+        .header("authorization", "Bearer old")
+        .on_unauthorized(move || {
+            *counter.lock().unwrap() += 1;
+            Some("new".to_string())
+        });
+    let response = client.execute(&config).unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "me");
+    assert_eq!(*calls.lock().unwrap(), 1);
+
+    // No token: the 401 comes back as it is
+    let config = RequestConfig::new(&url)
+        .header("Authorization", "Bearer old")
+        .on_unauthorized(|| None);
+    assert_eq!(client.execute(&config).unwrap().status, 401);
+
+    expired.assert();
+    refreshed.assert();
+}
+
+#[test]
+fn test_token_refresh_only_once() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/me")
+        .with_status(401)
+        .expect(2)
+        .create();
+
+    let client = MinimalCurl::new();
+    let calls = Arc::new(Mutex::new(0));
+    let counter = Arc::clone(&calls);
+    let config = RequestConfig::new(format!("{}/me", server.url()))
+        .fail_on_error(true)
+        .on_unauthorized(move || {
+            *counter.lock().unwrap() += 1;
+            Some("still-rejected".to_string())
+        });
+    match client.execute(&config) {
+        Err(CurlError::HttpStatus { status, .. }) => assert_eq!(status, 401),
+        other => panic!("expected a 401, got {:?}", other.map(|r| r.status)),
+    }
+    assert_eq!(*calls.lock().unwrap(), 1);
+    mock.assert();
+}
+
+#[test]
+fn test_repeated_response_headers() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/login")
+        .with_header("Set-Cookie", "session=abc; Path=/")
+        .with_header("Set-Cookie", "theme=dark")
+        .with_header("Vary", "Accept-Encoding")
+        .create();
+
+    let response = MinimalCurl::new()
+        .get(&format!("{}/login", server.url()))
+        .unwrap();
+    mock.assert();
+    assert_eq!(
+        response.get_all("set-cookie"),
+        ["session=abc; Path=/", "theme=dark"]
+    );
+    assert_eq!(
+        response.get_header("Set-Cookie").unwrap(),
+        "session=abc; Path=/"
+    );
+    assert_eq!(response.get_all("Vary"), ["Accept-Encoding"]);
+    assert!(response.get_all("Link").is_empty());
+}
+
+#[test]
+fn test_cookie_jar() {
+    let mut server = Server::new();
+    let login = server
+        .mock("POST", "/login")
+        .with_status(303)
+        .with_header("Location", "/home")
+        .with_header("Set-Cookie", "session=abc123; Path=/; HttpOnly")
+        .create();
+    let home = server
+        .mock("GET", "/home")
+        .match_header("cookie", "session=abc123")
+        .with_body("welcome")
+        .expect(2)
+        .create();
+    let merged = server
+        .mock("GET", "/prefs")
+        .match_header("cookie", "lang=en; session=abc123")
+        .create();
+
+    let client = MinimalCurl::new().with_cookies();
+    let response = client
+        .post(&format!("{}/login", server.url()), None)
+        .unwrap();
+    assert_eq!(response.text(), "welcome");
+    let response = client.get(&format!("{}/home", server.url())).unwrap();
+    assert_eq!(response.text(), "welcome");
+    let config = RequestConfig::new(format!("{}/prefs", server.url())).header("Cookie", "lang=en");
+    assert_eq!(client.execute(&config).unwrap().status, 200);
+    assert_eq!(client.cookies().unwrap().cookies()[0].name, "session");
+
+    login.assert();
+    home.assert();
+    merged.assert();
+
+    // Without a jar nothing is remembered
+    assert!(MinimalCurl::new().cookies().is_none());
+}
+
+#[test]
+fn test_cookie_file_round_trip() {
+    let mut server = Server::new();
+    let host = server.host_with_port();
+    let host = host.split(':').next().unwrap();
+    let mock = server
+        .mock("GET", "/me")
+        .match_header("cookie", "session=abc")
+        .with_header("Set-Cookie", "theme=dark")
+        .create();
+
+    let file = NamedTempFile::new().unwrap();
+    let path = file.path().to_str().unwrap();
+    std::fs::write(
+        path,
+        format!(
+            "# Netscape HTTP Cookie File\n{}\tFALSE\t/\tFALSE\t0\tsession\tabc\n",
+            host
+        ),
+    )
+    .unwrap();
+
+    let jar = CookieJar::from_iter(cookies::load_cookies(path).unwrap());
+    let client = MinimalCurl::new().with_cookie_jar(jar);
+    client.get(&format!("{}/me", server.url())).unwrap();
+    mock.assert();
+
+    cookies::save_cookies(path, &client.cookies().unwrap()).unwrap();
+    let saved = cookies::load_cookies(path).unwrap();
+    let names: Vec<&str> = saved.iter().map(|cookie| cookie.name.as_str()).collect();
+    assert_eq!(names, ["session", "theme"]);
 }
 
 #[test]
@@ -197,7 +395,7 @@ fn test_head_request() {
 
     mock.assert();
     assert_eq!(response.status, 200);
-    assert!(response.body.is_empty());
+    assert!(response.bytes().is_empty());
 }
 
 #[test]
@@ -261,7 +459,7 @@ fn test_large_response() {
 
     mock.assert();
     assert_eq!(response.status, 200);
-    assert_eq!(response.body.len(), 10000);
+    assert_eq!(response.bytes().len(), 10000);
 }
 
 #[test]
@@ -312,8 +510,8 @@ fn test_multiple_requests() {
 
     mock1.assert();
     mock2.assert();
-    assert_eq!(response1.body, "First response");
-    assert_eq!(response2.body, "Second response");
+    assert_eq!(response1.text(), "First response");
+    assert_eq!(response2.text(), "Second response");
 }
 
 #[test]
@@ -339,5 +537,1457 @@ fn test_request_config_chaining() {
 
     mock.assert();
     assert_eq!(response.status, 201);
-    assert_eq!(response.body, "Created");
+    assert_eq!(response.text(), "Created");
+}
+
+#[test]
+fn test_retry_transient_status() {
+    let mut server = Server::new();
+    let failing = server
+        .mock("GET", "/flaky")
+        .with_status(503)
+        .expect(1)
+        .create();
+    let succeeding = server
+        .mock("GET", "/flaky")
+        .with_status(200)
+        .with_body("Recovered")
+        .expect(1)
+        .create();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(format!("{}/flaky", server.url())).retries(2);
+    let response = client.execute(&config).unwrap();
+
+    failing.assert();
+    succeeding.assert();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "Recovered");
+}
+
+#[test]
+fn test_retry_custom_statuses() {
+    let mut server = Server::new();
+    let throttled = server
+        .mock("GET", "/busy")
+        .with_status(429)
+        .with_header("retry-after", "0")
+        .expect(1)
+        .create();
+    let teapot = server
+        .mock("GET", "/busy")
+        .with_status(418)
+        .expect(1)
+        .create();
+    let ok = server
+        .mock("GET", "/busy")
+        .with_status(200)
+        .expect(1)
+        .create();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(format!("{}/busy", server.url()))
+        .retries(3)
+        .retry_statuses([418, 429])
+        .retry_backoff(Duration::from_millis(10));
+    let start = std::time::Instant::now();
+    assert_eq!(client.execute(&config).unwrap().status, 200);
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    throttled.assert();
+    teapot.assert();
+    ok.assert();
+}
+
+#[test]
+fn test_retry_all_errors() {
+    let mut server = Server::new();
+    let not_found = server
+        .mock("GET", "/missing")
+        .with_status(404)
+        .expect(2)
+        .create();
+    let found = server
+        .mock("GET", "/missing")
+        .with_status(200)
+        .expect(1)
+        .create();
+
+    let client = MinimalCurl::new();
+    let url = format!("{}/missing", server.url());
+
+    // 404 is not transient, so a plain retry gives up immediately
+    let response = client
+        .execute(&RequestConfig::new(&url).retries(1))
+        .unwrap();
+    assert_eq!(response.status, 404);
+
+    let response = client
+        .execute(&RequestConfig::new(&url).retries(1).retry_all_errors(true))
+        .unwrap();
+
+    not_found.assert();
+    found.assert();
+    assert_eq!(response.status, 200);
+}
+
+#[test]
+fn test_header_injection_rejected() {
+    let mut server = Server::new();
+    let mock = server.mock("GET", "/").expect(0).create();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(server.url()).header("X-Evil", "a\r\nInjected: b");
+    let result = client.execute(&config);
+
+    mock.assert();
+    assert!(matches!(result, Err(CurlError::InvalidHeader(_))));
+}
+
+#[test]
+fn test_trace_id_header() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_header("X-Correlation-Id", "trace-123")
+        .with_status(200)
+        .create();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(server.url()).trace_id("X-Correlation-Id", "trace-123");
+    let response = client.execute(&config).unwrap();
+
+    mock.assert();
+    assert_eq!(response.status, 200);
+}
+
+#[test]
+fn test_accept_language() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_header("Accept-Language", "de-DE, de;q=0.9")
+        .with_status(200)
+        .with_header("Content-Language", "de")
+        .create();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(server.url()).accept_language("de-DE, de;q=0.9");
+    let response = client.execute(&config).unwrap();
+
+    mock.assert();
+    assert_eq!(
+        response.get_header("content-language"),
+        Some(&"de".to_string())
+    );
+}
+
+#[test]
+fn test_tls_failure_maps_to_tls_error() {
+    // Speaking TLS to a plain HTTP server makes the handshake fail
+    let server = Server::new();
+    let url = server.url().replacen("http://", "https://", 1);
+
+    let client = MinimalCurl::new();
+    let result = client.get(&url);
+
+    assert!(matches!(result, Err(CurlError::Tls(_))));
+}
+
+#[test]
+fn test_fail_on_error_keeps_body() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/missing")
+        .with_status(404)
+        .with_body("no such thing")
+        .create();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(format!("{}/missing", server.url())).fail_on_error(true);
+    let result = client.execute(&config);
+
+    mock.assert();
+    match result {
+        Err(CurlError::HttpStatus { status, body, .. }) => {
+            assert_eq!(status, 404);
+            assert_eq!(body, "no such thing");
+        }
+        other => panic!("expected HttpStatus error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_max_header_bytes() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .with_status(200)
+        .with_header("X-Padding", &"a".repeat(2000))
+        .with_body("OK")
+        .expect(2)
+        .create();
+
+    let client = MinimalCurl::new();
+    let result = client.execute(&RequestConfig::new(server.url()).max_header_bytes(1024));
+    assert!(matches!(
+        result,
+        Err(CurlError::HeadersTooLarge { limit: 1024, .. })
+    ));
+
+    let response = client
+        .execute(&RequestConfig::new(server.url()).max_header_bytes(8192))
+        .unwrap();
+
+    mock.assert();
+    assert_eq!(response.text(), "OK");
+}
+
+#[test]
+fn test_post_not_retried_without_retry_all_errors() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/submit")
+        .with_status(503)
+        .expect(1)
+        .create();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(format!("{}/submit", server.url()))
+        .method(HttpMethod::Post)
+        .data("payload")
+        .retries(2);
+    let response = client.execute(&config).unwrap();
+
+    mock.assert();
+    assert_eq!(response.status, 503);
+}
+
+#[test]
+fn test_not_modified_keeps_output_file() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_header("If-None-Match", "\"v1\"")
+        .with_status(304)
+        .create();
+
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+    std::fs::write(&temp_path, "cached body").unwrap();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(server.url())
+        .header("If-None-Match", "\"v1\"")
+        .output_file(&temp_path);
+    let response = client.execute(&config).unwrap();
+
+    mock.assert();
+    assert_eq!(response.status, 304);
+    assert_eq!(std::fs::read_to_string(&temp_path).unwrap(), "cached body");
+}
+
+#[test]
+fn test_http_proxy_with_credentials() {
+    // The mock server plays the proxy: it receives the absolute target URL
+    let mut proxy = Server::new();
+    let mock = proxy
+        .mock("GET", "/resource")
+        .match_header("Proxy-Authorization", "Basic dXNlcjpwYXNz")
+        .with_status(200)
+        .with_body("via proxy")
+        .create();
+
+    let options = ClientOptions::new()
+        .proxy(proxy.url())
+        .proxy_user("user:pass");
+    let client = MinimalCurl::with_options(options).unwrap();
+    let response = client.get("http://example.invalid/resource").unwrap();
+
+    mock.assert();
+    assert_eq!(response.text(), "via proxy");
+}
+
+#[test]
+fn test_env_proxy_and_no_proxy() {
+    let mut proxy = Server::new();
+    let proxied = proxy
+        .mock("GET", "/via-proxy")
+        .with_body("via proxy")
+        .create();
+    let mut direct = Server::new();
+    let bypassed = direct.mock("GET", "/direct").with_body("direct").create();
+
+    let proxies = EnvProxy {
+        http: Some(proxy.url()),
+        https: None,
+        no_proxy: vec!["127.0.0.1".to_string()],
+    };
+    let client = MinimalCurl::with_options(ClientOptions::new().env_proxy(proxies)).unwrap();
+
+    let response = client.get("http://example.invalid/via-proxy").unwrap();
+    assert_eq!(response.text(), "via proxy");
+    let response = client.get(&format!("{}/direct", direct.url())).unwrap();
+    assert_eq!(response.text(), "direct");
+    proxied.assert();
+    bypassed.assert();
+
+    let invalid = EnvProxy {
+        https: Some("ftp://proxy:21".to_string()),
+        ..EnvProxy::default()
+    };
+    let result = MinimalCurl::with_options(ClientOptions::new().env_proxy(invalid));
+    assert!(matches!(result, Err(CurlError::InvalidConfig(_))));
+}
+
+#[test]
+fn test_host_proxies() {
+    let mut regional = Server::new();
+    let routed = regional
+        .mock("GET", "/page")
+        .match_header("host", "shop.example.de")
+        .with_body("regional")
+        .create();
+    let mut direct = Server::new();
+    let direct_mock = direct.mock("GET", "/page").with_body("direct").create();
+
+    let options = ClientOptions::new().host_proxy("example.de", regional.url());
+    let client = MinimalCurl::with_options(options).unwrap();
+
+    let response = client.get("http://shop.example.de/page").unwrap();
+    assert_eq!(response.text(), "regional");
+    let response = client.get(&format!("{}/page", direct.url())).unwrap();
+    assert_eq!(response.text(), "direct");
+    routed.assert();
+    direct_mock.assert();
+
+    let options = ClientOptions::new().host_proxy("example.de", "ftp://proxy:21");
+    assert!(matches!(
+        MinimalCurl::with_options(options),
+        Err(CurlError::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn test_invalid_proxy_rejected() {
+    let result = MinimalCurl::with_options(ClientOptions::new().proxy("ftp://proxy:21"));
+    assert!(matches!(result, Err(CurlError::InvalidConfig(_))));
+}
+
+#[test]
+fn test_proxy_tunnel_auth_failure() {
+    // A proxy that turns down every CONNECT
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy = format!("http://{}", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        if let Ok((mut stream, _)) = listener.accept() {
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(
+                b"HTTP/1.1 407 Proxy Authentication Required\r\nContent-Length: 0\r\n\r\n",
+            );
+        }
+    });
+
+    let client =
+        MinimalCurl::with_options(ClientOptions::new().proxy(proxy).proxy_tunnel(true)).unwrap();
+    let result = client.get("https://example.invalid/");
+    assert!(
+        matches!(result, Err(CurlError::ProxyAuth(ref target)) if target == "example.invalid:443"),
+        "got {:?}",
+        result
+    );
+
+    // Plain HTTP can't be tunnelled, so it isn't sent at all
+    let result = client.get("http://example.invalid/");
+    assert!(matches!(result, Err(CurlError::InvalidConfig(_))));
+}
+
+#[test]
+fn test_insecure_per_request() {
+    // An HTTPS server with a self-signed certificate
+    let identity = native_tls::Identity::from_pkcs8(
+        include_bytes!("fixtures/self_signed.crt"),
+        include_bytes!("fixtures/self_signed.key"),
+    )
+    .unwrap();
+    let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!(
+        "https://localhost:{}/",
+        listener.local_addr().unwrap().port()
+    );
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // The verifying client hangs up during the handshake
+            let Ok(mut stream) = acceptor.accept(stream) else {
+                continue;
+            };
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+        }
+    });
+
+    let client = MinimalCurl::new();
+    let result = client.execute(&RequestConfig::new(&url));
+    assert!(result.is_err(), "self-signed certificate was accepted");
+
+    let response = client
+        .execute(&RequestConfig::new(&url).insecure(true))
+        .unwrap();
+    assert_eq!(response.text(), "ok");
+
+    // The insecure connection isn't reused for a verified request
+    assert!(client.execute(&RequestConfig::new(&url)).is_err());
+
+    // Or insecure for everything, connect timeout or not
+    let client = MinimalCurl::with_options(ClientOptions::new().insecure(true)).unwrap();
+    assert_eq!(client.get(&url).unwrap().text(), "ok");
+    let config = RequestConfig::new(&url).connect_timeout(Duration::from_secs(5));
+    assert_eq!(client.execute(&config).unwrap().text(), "ok");
+}
+
+#[test]
+fn test_connect_timeout_does_not_limit_reading() {
+    // Connects at once, but takes longer than the connect timeout to answer
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        if let Ok((mut stream, _)) = listener.accept() {
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            std::thread::sleep(Duration::from_millis(300));
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nslow");
+        }
+    });
+
+    let config = RequestConfig::new(&url)
+        .connect_timeout(Duration::from_millis(100))
+        .timeout(Duration::from_secs(5));
+    let response = MinimalCurl::new().execute(&config).unwrap();
+    assert_eq!(response.text(), "slow");
+}
+
+/// Serve one request with `head`, then send `body` a byte at a time every
+/// `delay`, and keep the connection open for a while after
+fn trickle_server(head: &'static str, body: &'static [u8], delay: Duration) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        if let Ok((mut stream, _)) = listener.accept() {
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(head.as_bytes());
+            for byte in body {
+                std::thread::sleep(delay);
+                let _ = stream.write_all(std::slice::from_ref(byte));
+            }
+            std::thread::sleep(Duration::from_secs(5));
+        }
+    });
+    url
+}
+
+#[test]
+fn test_speed_limit_slow_body() {
+    // About 20 bytes/sec against a limit of 1000
+    let url = trickle_server(
+        "HTTP/1.1 200 OK\r\nContent-Length: 40\r\n\r\n",
+        b"0123456789012345678901234567890123456789",
+        Duration::from_millis(50),
+    );
+    let config = RequestConfig::new(&url)
+        .speed_limit(1000, Duration::from_millis(300))
+        .timeout(Duration::from_secs(10));
+    let start = std::time::Instant::now();
+    let result = MinimalCurl::new().execute(&config);
+    assert!(
+        matches!(result, Err(CurlError::TooSlow { limit: 1000, .. })),
+        "got {:?}",
+        result
+    );
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn test_speed_limit_stalled_body() {
+    // A few bytes, then nothing while the connection stays open
+    let url = trickle_server(
+        "HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n",
+        b"abc",
+        Duration::ZERO,
+    );
+    let config = RequestConfig::new(&url)
+        .speed_limit(1, Duration::from_millis(300))
+        .timeout(Duration::from_secs(10));
+    let start = std::time::Instant::now();
+    let result = MinimalCurl::new().execute(&config);
+    assert!(
+        matches!(result, Err(CurlError::TooSlow { .. })),
+        "got {:?}",
+        result
+    );
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn test_speed_limit_fast_body() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .with_body("x".repeat(100_000))
+        .create();
+    let config = RequestConfig::new(server.url()).speed_limit(1024, Duration::from_millis(200));
+    let response = MinimalCurl::new().execute(&config).unwrap();
+    assert_eq!(response.body_bytes.len(), 100_000);
+    mock.assert();
+
+    let config = RequestConfig::new(server.url()).speed_limit(1, Duration::ZERO);
+    assert!(matches!(
+        MinimalCurl::new().execute(&config),
+        Err(CurlError::InvalidConfig(_))
+    ));
+}
+
+/// Answers 503 for the first `failures` calls, then 200
+struct FlakyTransport {
+    failures: usize,
+    calls: AtomicUsize,
+}
+
+impl Transport for FlakyTransport {
+    fn execute(&self, config: &RequestConfig) -> Result<CurlResponse, CurlError> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        let status = if call < self.failures { 503 } else { 200 };
+        Ok(CurlResponse {
+            status,
+            status_text: String::new(),
+            headers: HashMap::new(),
+            body: format!("{} {}", config.method, config.url),
+            ..Default::default()
+        })
+    }
+}
+
+#[test]
+fn test_mock_transport() {
+    let client = MinimalCurl::with_transport(Box::new(FlakyTransport {
+        failures: 1,
+        calls: AtomicUsize::new(0),
+    }));
+
+    // No server is listening here; the transport answers instead
+    let config = RequestConfig::new("http://example.invalid/items").retries(1);
+    let response = client.execute(&config).unwrap();
+
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "GET http://example.invalid/items");
+    // Timed across both attempts, including the delay between them
+    assert!(response.elapsed >= std::time::Duration::from_secs(1));
+}
+
+#[test]
+fn test_execute_with_retry_policy() {
+    let client = MinimalCurl::with_transport(Box::new(FlakyTransport {
+        failures: 2,
+        calls: AtomicUsize::new(0),
+    }));
+    let policy = RetryPolicy::default()
+        .backoff(Duration::from_millis(10))
+        .jitter(Duration::from_millis(10));
+
+    // The policy's retries apply, not the config's
+    let config = RequestConfig::new("http://example.invalid/items").retries(0);
+    let response = client.execute_with_retry(&config, &policy).unwrap();
+    assert_eq!(response.status, 200);
+
+    let client = MinimalCurl::with_transport(Box::new(FlakyTransport {
+        failures: 2,
+        calls: AtomicUsize::new(0),
+    }));
+    let response = client
+        .execute_with_retry(&config, &policy.clone().statuses([500]))
+        .unwrap();
+    assert_eq!(response.status, 503);
+}
+
+/// Answers 503 every time, noting the timeout each attempt was given
+struct TimeoutRecorder(Arc<Mutex<Vec<Option<Duration>>>>);
+
+impl Transport for TimeoutRecorder {
+    fn execute(&self, config: &RequestConfig) -> Result<CurlResponse, CurlError> {
+        self.0.lock().unwrap().push(config.timeout);
+        Ok(CurlResponse {
+            status: 503,
+            ..Default::default()
+        })
+    }
+}
+
+#[test]
+fn test_attempt_timeout_within_budget() {
+    let timeouts = Arc::new(Mutex::new(Vec::new()));
+    let client = MinimalCurl::with_transport(Box::new(TimeoutRecorder(Arc::clone(&timeouts))));
+
+    // 1.5s in total: the first retry still fits, with what's left of the budget
+    let config = RequestConfig::new("http://example.invalid/")
+        .retries(5)
+        .timeout(Duration::from_millis(1500))
+        .attempt_timeout(Duration::from_secs(1));
+    let response = client.execute(&config).unwrap();
+    assert_eq!(response.status, 503);
+
+    let timeouts = timeouts.lock().unwrap();
+    assert_eq!(timeouts.len(), 2);
+    assert_eq!(timeouts[0], Some(Duration::from_secs(1)));
+    assert!(timeouts[1].unwrap() <= Duration::from_millis(500));
+}
+
+#[test]
+fn test_probe_ranges() {
+    let mut server = Server::new();
+    server
+        .mock("HEAD", "/video")
+        .with_header("accept-ranges", "bytes")
+        .create();
+    server.mock("HEAD", "/stream").create();
+    server.mock("HEAD", "/gone").with_status(404).create();
+
+    let client = MinimalCurl::new();
+    assert!(client
+        .probe_ranges(&format!("{}/video", server.url()))
+        .unwrap());
+    assert!(!client
+        .probe_ranges(&format!("{}/stream", server.url()))
+        .unwrap());
+    assert!(matches!(
+        client.probe_ranges(&format!("{}/gone", server.url())),
+        Err(CurlError::HttpStatus { status: 404, .. })
+    ));
+}
+
+#[test]
+fn test_segmented_download() {
+    let mut server = Server::new();
+    let content = "0123456789";
+    let head = server
+        .mock("HEAD", "/file")
+        .with_status(200)
+        .with_header("accept-ranges", "bytes")
+        .with_header("content-length", "10")
+        .create();
+    let first = server
+        .mock("GET", "/file")
+        .match_header("Range", "bytes=0-4")
+        .with_status(206)
+        .with_body(&content[0..5])
+        .create();
+    let second = server
+        .mock("GET", "/file")
+        .match_header("Range", "bytes=5-9")
+        .with_status(206)
+        .with_body(&content[5..])
+        .create();
+
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(format!("{}/file", server.url())).output_file(&temp_path);
+    let (bytes, segments) = client.download_segmented(&config, 2).unwrap();
+
+    head.assert();
+    first.assert();
+    second.assert();
+    assert_eq!((bytes, segments), (10, 2));
+    assert_eq!(std::fs::read_to_string(&temp_path).unwrap(), content);
+}
+
+#[cfg(feature = "charset")]
+#[test]
+fn test_legacy_charset_decoded() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .with_status(200)
+        .with_header("content-type", "text/plain; charset=iso-8859-1")
+        .with_body(b"caf\xe9")
+        .create();
+
+    let client = MinimalCurl::new();
+    let response = client.get(&server.url()).unwrap();
+
+    mock.assert();
+    assert_eq!(response.charset(), Some("iso-8859-1"));
+    assert_eq!(response.text(), "café");
+}
+
+#[test]
+fn test_redirect_see_other_becomes_get() {
+    let mut server = Server::new();
+    let redirect = server
+        .mock("POST", "/submit")
+        .with_status(303)
+        .with_header("location", "/done")
+        .create();
+    let done = server
+        .mock("GET", "/done")
+        .match_body(Matcher::Exact(String::new()))
+        .with_body("Done")
+        .create();
+
+    let client = MinimalCurl::new();
+    let response = client
+        .post(&format!("{}/submit", server.url()), Some("payload"))
+        .unwrap();
+
+    redirect.assert();
+    done.assert();
+    assert_eq!(response.text(), "Done");
+}
+
+#[test]
+fn test_redirect_loop_detected() {
+    let mut server = Server::new();
+    let a = server
+        .mock("GET", "/a")
+        .with_status(302)
+        .with_header("location", "/b")
+        .expect(1)
+        .create();
+    let b = server
+        .mock("GET", "/b")
+        .with_status(302)
+        .with_header("location", "/a")
+        .expect(1)
+        .create();
+
+    let client = MinimalCurl::new();
+    let url = format!("{}/a", server.url());
+    let config = RequestConfig::new(&url).abort_on_redirect_loop(true);
+
+    match client.execute(&config) {
+        Err(CurlError::RedirectLoop { urls }) => {
+            assert_eq!(urls, [url.clone(), format!("{}/b", server.url()), url]);
+        }
+        other => panic!("expected RedirectLoop, got {:?}", other),
+    }
+    a.assert();
+    b.assert();
+
+    // Without the flag the loop runs until the redirect limit
+    let result = client.execute(&RequestConfig::new(format!("{}/a", server.url())));
+    assert!(matches!(result, Err(CurlError::TooManyRedirects(10))));
+}
+
+#[test]
+fn test_cloned_client_across_threads() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/shared")
+        .with_body("ok")
+        .expect(4)
+        .create();
+
+    let client = MinimalCurl::new();
+    let url = format!("{}/shared", server.url());
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let client = client.clone();
+            let url = url.clone();
+            std::thread::spawn(move || client.get(&url).unwrap().text().to_string())
+        })
+        .collect();
+
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), "ok");
+    }
+    mock.assert();
+}
+
+#[test]
+fn test_multipart_upload() {
+    let file = NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), "a,b\n1,2\n").unwrap();
+
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/upload")
+        .match_header(
+            "content-type",
+            Matcher::Regex("^multipart/form-data; boundary=".to_string()),
+        )
+        // Streamed with a known length rather than chunked
+        .match_header("content-length", Matcher::Regex(r"^\d+$".to_string()))
+        .match_header("transfer-encoding", Matcher::Missing)
+        .match_body(Matcher::AllOf(vec![
+            Matcher::Regex("name=\"title\"\r\n\r\nQ3\r\n".to_string()),
+            Matcher::Regex(
+                "name=\"data\"; filename=\"data.csv\"\r\nContent-Type: text/csv\r\n\r\na,b\n1,2\n\r\n"
+                    .to_string(),
+            ),
+        ]))
+        .with_status(201)
+        .create();
+
+    let spec = format!(
+        "data=@{};type=text/csv;filename=data.csv",
+        file.path().display()
+    );
+    let config = RequestConfig::new(format!("{}/upload", server.url()))
+        .method(HttpMethod::Post)
+        .multipart(vec![
+            MultipartPart::text("title", "Q3"),
+            MultipartPart::parse(&spec).unwrap(),
+        ]);
+    let response = MinimalCurl::new().execute(&config).unwrap();
+
+    mock.assert();
+    assert_eq!(response.status, 201);
+}
+
+#[test]
+fn test_redirected_response_records_urls() {
+    let mut server = Server::new();
+    server
+        .mock("GET", "/old")
+        .with_status(302)
+        .with_header("location", "/new")
+        .create();
+    server.mock("GET", "/new").with_body("moved").create();
+
+    let client = MinimalCurl::new();
+    let response = client.get(&format!("{}/old", server.url())).unwrap();
+    assert!(response.redirected());
+    assert_eq!(response.original_url, format!("{}/old", server.url()));
+    assert_eq!(response.final_url, format!("{}/new", server.url()));
+    assert_eq!(response.text(), "moved");
+
+    let response = client.get(&format!("{}/new", server.url())).unwrap();
+    assert!(!response.redirected());
+}
+
+#[test]
+fn test_binary_body_sent_unchanged() {
+    let payload = vec![0x89, b'P', b'N', b'G', 0x00, 0xff, 0xfe];
+    let mut server = Server::new();
+    let mock = server
+        .mock("PUT", "/image")
+        .match_body(Matcher::from(payload.clone()))
+        .with_status(204)
+        .create();
+
+    let config = RequestConfig::new(format!("{}/image", server.url()))
+        .method(HttpMethod::Put)
+        .body_bytes(payload);
+    let response = MinimalCurl::new().execute(&config).unwrap();
+
+    mock.assert();
+    assert_eq!(response.status, 204);
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_execute_lines_gzip_ndjson() {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let ndjson = "{\"id\":1}\n{\"id\":2}\r\n{\"id\":3}\n";
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(ndjson.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/events")
+        .match_header("accept-encoding", Matcher::Regex("gzip".to_string()))
+        .with_header("content-encoding", "gzip")
+        .with_header("content-type", "application/x-ndjson")
+        .with_body(compressed)
+        .create();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(format!("{}/events", server.url()));
+    let lines: Vec<String> = client
+        .execute_lines(&config)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    mock.assert();
+    assert_eq!(lines, ["{\"id\":1}", "{\"id\":2}", "{\"id\":3}"]);
+}
+
+/// Answers 200 with the same body every time
+struct CannedTransport(Vec<u8>);
+
+impl Transport for CannedTransport {
+    fn execute(&self, _config: &RequestConfig) -> Result<CurlResponse, CurlError> {
+        Ok(CurlResponse {
+            status: 200,
+            body: String::from_utf8_lossy(&self.0).into_owned(),
+            body_bytes: self.0.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+#[test]
+fn test_execute_lines_non_utf8_matches_transport() {
+    let ndjson = b"{\"id\":1}\n{\"id\":\xff}\n".to_vec();
+    let mut server = Server::new();
+    server
+        .mock("GET", "/events")
+        .with_body(ndjson.clone())
+        .create();
+
+    // Both paths read the bytes as received, so the bad line is an error
+    // rather than a line with U+FFFD in it
+    let over_http = MinimalCurl::new()
+        .execute_lines(&RequestConfig::new(format!("{}/events", server.url())))
+        .unwrap()
+        .collect::<Vec<_>>();
+    let over_transport = MinimalCurl::with_transport(Box::new(CannedTransport(ndjson)))
+        .execute_lines(&RequestConfig::new("http://example.invalid/events"))
+        .unwrap()
+        .collect::<Vec<_>>();
+    for lines in [over_http, over_transport] {
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].as_ref().unwrap(), "{\"id\":1}");
+        assert_eq!(
+            lines[1].as_ref().unwrap_err().kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+}
+
+#[test]
+fn test_decode_body_off_keeps_raw_bytes() {
+    // Not valid gzip: with decoding off it must come back untouched either way
+    let raw = vec![0x1f, 0x8b, 0x08, 0x00, 0xff, 0x00];
+    let mut server = Server::new();
+    server
+        .mock("GET", "/archive")
+        .with_header("content-encoding", "gzip")
+        .with_body(raw.clone())
+        .create();
+
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+    let config = RequestConfig::new(format!("{}/archive", server.url()))
+        .decode_body(false)
+        .output_file(&temp_path)
+        .keep_body(true);
+    let response = MinimalCurl::new().execute(&config).unwrap();
+
+    assert_eq!(response.body_bytes, raw);
+    assert_eq!(response.get_header("content-encoding").unwrap(), "gzip");
+    assert_eq!(std::fs::read(&temp_path).unwrap(), raw);
+}
+
+#[test]
+fn test_file_url() {
+    let file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+    std::fs::write(file.path(), "{\"local\":true}").unwrap();
+    let url = url::Url::from_file_path(file.path()).unwrap().to_string();
+
+    let client = MinimalCurl::new();
+    let response = client.get(&url).unwrap();
+    assert_eq!(response.status, 200);
+    assert_eq!(response.text(), "{\"local\":true}");
+    assert_eq!(
+        response.get_header("content-type").unwrap(),
+        "application/json"
+    );
+    assert_eq!(response.get_header("content-length").unwrap(), "14");
+
+    // The scheme is matched in any case, as validation parses it
+    let upper = url.replacen("file:", "FILE:", 1);
+    assert_eq!(client.get(&upper).unwrap().text(), "{\"local\":true}");
+
+    let lines: Vec<String> = client
+        .execute_lines(&RequestConfig::new(&upper))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(lines, ["{\"local\":true}"]);
+
+    let missing = format!("{}.missing", url);
+    assert!(matches!(client.get(&missing), Err(CurlError::IoError(_))));
+    assert!(matches!(
+        client.post(&url, Some("x")),
+        Err(CurlError::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn test_expect_status() {
+    let mut server = Server::new();
+    server
+        .mock("POST", "/items")
+        .with_status(200)
+        .expect(2)
+        .create();
+
+    let client = MinimalCurl::new();
+    let config = RequestConfig::new(format!("{}/items", server.url()))
+        .method(HttpMethod::Post)
+        .expect_status(201);
+    match client.execute(&config) {
+        Err(CurlError::UnexpectedStatus { expected, actual }) => {
+            assert_eq!((expected, actual), (201, 200));
+        }
+        other => panic!("expected UnexpectedStatus, got {:?}", other),
+    }
+
+    let response = client.execute(&config.expect_status(200)).unwrap();
+    assert_eq!(response.status, 200);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_json_typed_body() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/items")
+        .match_header("content-type", "application/json")
+        .match_body(Matcher::Json(
+            serde_json::json!({"name": "widget", "qty": 2}),
+        ))
+        .with_status(201)
+        .create();
+
+    let body = serde_json::json!({"name": "widget", "qty": 2});
+    let config = RequestConfig::new(format!("{}/items", server.url()))
+        .method(HttpMethod::Post)
+        .body_typed(bcurl::Json(body))
+        .unwrap();
+    let response = MinimalCurl::new().execute(&config).unwrap();
+
+    mock.assert();
+    assert_eq!(response.status, 201);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_parse_json_body() {
+    let mut server = Server::new();
+    server
+        .mock("GET", "/item")
+        .with_header("content-type", "application/vnd.api+json")
+        .with_body(r#"{"name": "widget", "qty": 2}"#)
+        .create();
+    server
+        .mock("GET", "/page")
+        .with_header("content-type", "text/html")
+        .with_body("<p>hi</p>")
+        .create();
+
+    let client = MinimalCurl::new();
+    let response = client.get(&format!("{}/item", server.url())).unwrap();
+    let item: serde_json::Value = response.parse_as::<_, bcurl::JsonDeserializer>().unwrap();
+    assert_eq!(item["qty"], 2);
+    let item: HashMap<String, serde_json::Value> = response.parse_auto().unwrap();
+    assert_eq!(item["name"], "widget");
+
+    let response = client.get(&format!("{}/page", server.url())).unwrap();
+    let result: Result<serde_json::Value, _> = response.parse_auto();
+    assert!(matches!(result, Err(CurlError::DeserializeError(_))));
+    let result = response.parse_as::<serde_json::Value, bcurl::JsonDeserializer>();
+    assert!(matches!(result, Err(CurlError::DeserializeError(_))));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_json_response() {
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Item {
+        name: String,
+        qty: u32,
+    }
+
+    let mut server = Server::new();
+    server
+        .mock("GET", "/item")
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"name": "widget", "qty": 2}"#)
+        .create();
+    server
+        .mock("GET", "/broken")
+        .with_body("{\"name\":")
+        .create();
+
+    let client = MinimalCurl::new();
+    let item: Item = client
+        .get(&format!("{}/item", server.url()))
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(
+        item,
+        Item {
+            name: "widget".to_string(),
+            qty: 2
+        }
+    );
+
+    let response = client.get(&format!("{}/broken", server.url())).unwrap();
+    match response.json::<Item>() {
+        Err(CurlError::DeserializeError(message)) => {
+            assert!(message.contains("EOF"), "{}", message)
+        }
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_query_params() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/search")
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("page".into(), "2".into()),
+            Matcher::UrlEncoded("q".into(), "a&b c".into()),
+        ]))
+        .create();
+
+    let config = RequestConfig::new(format!("{}/search?page=2", server.url())).query("q", "a&b c");
+    let response = MinimalCurl::new().execute(&config).unwrap();
+
+    mock.assert();
+    assert!(response.final_url.ends_with("/search?page=2&q=a%26b%20c"));
+}
+
+#[test]
+fn test_default_headers() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/me")
+        .match_header("x-api-key", "secret")
+        .match_header("user-agent", "override/1.0")
+        .create();
+
+    let client = MinimalCurl::new().with_default_headers(vec![
+        ("X-Api-Key".to_string(), "secret".to_string()),
+        ("User-Agent".to_string(), "defaults/1.0".to_string()),
+    ]);
+    let config =
+        RequestConfig::new(format!("{}/me", server.url())).header("user-agent", "override/1.0");
+    let response = client.execute(&config).unwrap();
+
+    mock.assert();
+    assert_eq!(response.status, 200);
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_compression_on_by_default() {
+    let mut server = Server::new();
+    let compressed = server
+        .mock("GET", "/data")
+        .match_header("accept-encoding", "gzip, deflate")
+        .create();
+    let plain = server
+        .mock("GET", "/data")
+        .match_header("accept-encoding", Matcher::Missing)
+        .create();
+
+    // A library default config asks for compression, as the CLI does
+    let client = MinimalCurl::new();
+    let url = format!("{}/data", server.url());
+    client.execute(&RequestConfig::new(&url)).unwrap();
+    client
+        .execute(&RequestConfig::new(&url).compression(false))
+        .unwrap();
+
+    compressed.assert();
+    plain.assert();
+}
+
+#[test]
+fn test_minimal_headers() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/raw")
+        .match_header("accept-encoding", Matcher::Missing)
+        .match_header("x-probe", "1")
+        .match_header("host", Matcher::Any)
+        .create();
+
+    // Compression is on by default but its Accept-Encoding is dropped
+    let config = RequestConfig::new(format!("{}/raw", server.url()))
+        .header("X-Probe", "1")
+        .minimal_headers(true);
+    assert_eq!(MinimalCurl::new().execute(&config).unwrap().status, 200);
+    mock.assert();
+}
+
+#[test]
+fn test_ensure_charset() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("POST", "/users")
+        .match_header("content-type", "application/json; charset=utf-8")
+        .match_body(r#"{"name":"Zoë"}"#)
+        .expect(2)
+        .create();
+
+    let client = MinimalCurl::new();
+    let url = format!("{}/users", server.url());
+    let config = RequestConfig::new(&url)
+        .method(HttpMethod::Post)
+        .header("Content-Type", "application/json")
+        .data(r#"{"name":"Zoë"}"#)
+        .ensure_charset(true);
+    client.execute(&config).unwrap();
+
+    // A charset that's already there is left alone
+    let config = config.header("Content-Type", "application/json; charset=utf-8");
+    client.execute(&config).unwrap();
+    mock.assert();
+}
+
+#[test]
+fn test_body_file_streamed() {
+    let mut upload = NamedTempFile::new().unwrap();
+    let payload: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    upload.write_all(&payload).unwrap();
+
+    let mut server = Server::new();
+    let mock = server
+        .mock("PUT", "/upload")
+        .match_header("content-length", "200000")
+        .match_header("transfer-encoding", Matcher::Missing)
+        .match_body(payload)
+        .create();
+
+    let client = MinimalCurl::new();
+    let url = format!("{}/upload", server.url());
+    let config = RequestConfig::new(&url)
+        .method(HttpMethod::Put)
+        .body_file(upload.path().to_str().unwrap());
+    assert_eq!(client.execute(&config).unwrap().status, 200);
+    mock.assert();
+
+    let config = RequestConfig::new(&url)
+        .method(HttpMethod::Put)
+        .body_file("/nonexistent/upload.bin");
+    assert!(matches!(
+        client.execute(&config),
+        Err(CurlError::IoError(_))
+    ));
+}
+
+#[test]
+fn test_checksum_verification() {
+    let mut server = Server::new();
+    server.mock("GET", "/file").with_body("abc").create();
+    server
+        .mock("GET", "/corrupt")
+        .with_header("content-md5", "AAAAAAAAAAAAAAAAAAAAAA==")
+        .with_body("abc")
+        .create();
+
+    let client = MinimalCurl::new();
+    let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    let config = RequestConfig::new(format!("{}/file", server.url()))
+        .verify_checksum(Algorithm::Sha256, sha256)
+        .unwrap();
+    assert_eq!(client.execute(&config).unwrap().text(), "abc");
+
+    // A wrong digest fails without touching the output file
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+    let config = RequestConfig::new(format!("{}/file", server.url()))
+        .output_file(&temp_path)
+        .verify_checksum(Algorithm::Md5, "d41d8cd98f00b204e9800998ecf8427e")
+        .unwrap();
+    match client.execute(&config) {
+        Err(CurlError::ChecksumMismatch { actual, .. }) => {
+            assert_eq!(actual, "900150983cd24fb0d6963f7d28e17f72");
+        }
+        other => panic!("expected ChecksumMismatch, got {:?}", other),
+    }
+    assert_eq!(std::fs::read(&temp_path).unwrap(), b"");
+
+    // Content-MD5 is checked even without an expected digest
+    let result = client.get(&format!("{}/corrupt", server.url()));
+    assert!(matches!(result, Err(CurlError::ChecksumMismatch { .. })));
+}
+
+#[test]
+fn test_large_download_streamed_to_file() {
+    let body: Vec<u8> = (0..4 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let mut server = Server::new();
+    server
+        .mock("GET", "/large.bin")
+        .with_header("content-type", "application/octet-stream")
+        .with_body(body.clone())
+        .create();
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("large.bin");
+    let config = RequestConfig::new(format!("{}/large.bin", server.url()))
+        .output_file(path.to_str().unwrap());
+    let response = MinimalCurl::new().execute(&config).unwrap();
+
+    // The body went to disk without being held in the response
+    assert_eq!(response.streamed_to_file, Some(body.len() as u64));
+    assert!(response.body.is_empty());
+    assert!(response.body_bytes.is_empty());
+    assert!(!response.is_empty_body());
+    assert_eq!(response.content_length_mismatch(), None);
+    assert!(std::fs::read(&path).unwrap() == body);
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_discard_body() {
+    let body = "x".repeat(4 * 1024 * 1024);
+    let mut server = Server::new();
+    server.mock("GET", "/large").with_body(&body).create();
+
+    let config = RequestConfig::new(format!("{}/large", server.url())).discard_body(true);
+    let response = MinimalCurl::new().execute(&config).unwrap();
+
+    // The body was read to the end but not kept
+    assert_eq!(response.status, 200);
+    assert_eq!(response.discarded, Some(body.len() as u64));
+    assert!(response.body.is_empty());
+    assert!(response.body_bytes.is_empty());
+    assert!(!response.is_empty_body());
+    assert_eq!(response.content_length_mismatch(), None);
+}
+
+#[test]
+fn test_progress_callback() {
+    let body = "x".repeat(200_000);
+    let mut server = Server::new();
+    server.mock("GET", "/sized").with_body(&body).create();
+    server
+        .mock("GET", "/chunked")
+        .with_chunked_body(|w| w.write_all(b"abc"))
+        .create();
+
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&calls);
+    let config = RequestConfig::new(format!("{}/sized", server.url()))
+        .on_progress(move |done, total| log.lock().unwrap().push((done, total)));
+    MinimalCurl::new().execute(&config).unwrap();
+
+    let calls = std::mem::take(&mut *calls.lock().unwrap());
+    assert!(calls.len() > 1);
+    assert!(calls.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert!(calls.iter().all(|&(_, total)| total == Some(200_000)));
+    assert_eq!(calls.last(), Some(&(200_000, Some(200_000))));
+
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let log = Arc::clone(&calls);
+    let config = RequestConfig::new(format!("{}/chunked", server.url()))
+        .on_progress(move |done, total| log.lock().unwrap().push((done, total)));
+    MinimalCurl::new().execute(&config).unwrap();
+    assert_eq!(calls.lock().unwrap().last(), Some(&(3, None)));
+}
+
+#[test]
+fn test_binary_body_saved_intact() {
+    let png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0xff];
+    let mut server = Server::new();
+    server
+        .mock("GET", "/logo.png")
+        .with_header("content-type", "image/png")
+        .with_body(png.clone())
+        .create();
+
+    let temp_file = NamedTempFile::new().unwrap();
+    let temp_path = temp_file.path().to_str().unwrap().to_string();
+    let config = RequestConfig::new(format!("{}/logo.png", server.url()))
+        .output_file(&temp_path)
+        .keep_body(true);
+    let response = MinimalCurl::new().execute(&config).unwrap();
+
+    assert_eq!(response.bytes(), png);
+    assert!(response.text().contains('\u{fffd}'));
+    assert_eq!(response.content_length_mismatch(), None);
+    assert_eq!(std::fs::read(&temp_path).unwrap(), png);
+}
+
+#[test]
+fn test_pool_stats_count_reuse() {
+    let mut server = Server::new();
+    server.mock("GET", "/").expect(3).create();
+
+    let client = MinimalCurl::new();
+    for _ in 0..3 {
+        client.get(&server.url()).unwrap();
+    }
+
+    let stats = client.clone().pool_stats();
+    assert_eq!(stats.opened() + stats.reused(), 3);
+    assert!(stats.opened() >= 1);
+    assert_eq!(stats.hosts.len(), 1);
+}
+
+#[test]
+fn test_on_redirect_policy() {
+    let mut server = Server::new();
+    server
+        .mock("GET", "/start")
+        .with_status(302)
+        .with_header("location", "/next")
+        .create();
+    server
+        .mock("GET", "/next")
+        .with_status(301)
+        .with_header("location", "https://elsewhere.invalid/")
+        .create();
+
+    let client = MinimalCurl::new();
+    let base = server.url();
+    let allowed = base.clone();
+    let config = RequestConfig::new(format!("{}/start", base)).on_redirect(move |redirect| {
+        if redirect.to.starts_with(&allowed) {
+            RedirectAction::Follow
+        } else {
+            RedirectAction::Stop
+        }
+    });
+    let response = client.execute(&config).unwrap();
+    assert_eq!(response.status, 301);
+    assert_eq!(response.final_url, format!("{}/next", base));
+
+    let config = RequestConfig::new(format!("{}/start", base))
+        .on_redirect(|_: &Redirect| RedirectAction::Error);
+    match client.execute(&config) {
+        Err(CurlError::RedirectRefused { from, to }) => {
+            assert_eq!(from, format!("{}/start", base));
+            assert_eq!(to, format!("{}/next", base));
+        }
+        other => panic!("expected RedirectRefused, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_shared_pool_across_clients() {
+    let mut server = Server::new();
+    let mock = server
+        .mock("GET", "/")
+        .match_header("x-service", Matcher::Any)
+        .expect(2)
+        .create();
+
+    let pool = SharedPool::new(ClientOptions::new()).unwrap();
+    let first = MinimalCurl::with_shared_pool(&pool)
+        .with_default_headers(vec![("X-Service".to_string(), "first".to_string())]);
+    let second = MinimalCurl::with_shared_pool(&first.shared_pool())
+        .with_default_headers(vec![("X-Service".to_string(), "second".to_string())]);
+    first.get(&server.url()).unwrap();
+    second.get(&server.url()).unwrap();
+    mock.assert();
+
+    // Both clients count towards the one pool
+    let stats = MinimalCurl::with_shared_pool(&pool).pool_stats();
+    assert_eq!(stats.opened() + stats.reused(), 2);
+    assert_eq!(first.pool_stats(), second.pool_stats());
 }